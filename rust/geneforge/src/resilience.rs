@@ -1,6 +1,9 @@
 //! Resilience patterns for genomics pipelines
 
 use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
 
 
 pub fn should_shed_load(in_flight: usize, limit: usize) -> bool {
@@ -31,6 +34,30 @@ pub struct ReplayEvent {
     pub samples_delta: i64,
 }
 
+impl ReplayEvent {
+    /// Build an event whose idempotency key is derived from its content, so two
+    /// deltas with identical payloads collapse during replay even if upstream
+    /// assigned them different keys. Uses the same digest scheme as the version
+    /// chunk store.
+    pub fn with_content_key(version: i64, findings_delta: i64, samples_delta: i64) -> Self {
+        Self {
+            idempotency_key: content_idempotency_key(version, findings_delta, samples_delta),
+            version,
+            findings_delta,
+            samples_delta,
+        }
+    }
+}
+
+/// Content hash of a replay delta, usable as a collapsing idempotency key.
+pub fn content_idempotency_key(version: i64, findings_delta: i64, samples_delta: i64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(version.to_le_bytes());
+    hasher.update(findings_delta.to_le_bytes());
+    hasher.update(samples_delta.to_le_bytes());
+    hex::encode(hasher.finalize())
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ReplaySnapshot {
     pub findings: i64,
@@ -78,11 +105,31 @@ pub fn replay_sequence(
 }
 
 
+/// One second of the sliding window: the second it represents plus the
+/// success/failure tallies recorded during it.
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    second: u64,
+    successes: u32,
+    failures: u32,
+}
+
 #[derive(Debug, Clone)]
 pub struct CircuitBreaker {
-    pub failure_count: usize,
-    pub threshold: usize,
     pub state: CircuitState,
+    /// Length in seconds of the sliding window the failure ratio is measured
+    /// over (also the number of per-second buckets retained).
+    pub window_secs: usize,
+    /// Open once the windowed failure ratio meets or exceeds this.
+    failure_ratio_threshold: f64,
+    /// How long Open lasts before a single trial request is allowed through.
+    open_timeout: Duration,
+    /// Start of the clock the per-second buckets are indexed against.
+    epoch: Instant,
+    /// Ring of per-second buckets covering the last `buckets.len()` seconds.
+    buckets: Vec<Bucket>,
+    /// When the breaker last transitioned to Open.
+    opened_at: Option<Instant>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -94,39 +141,123 @@ pub enum CircuitState {
 
 impl CircuitBreaker {
     pub fn new() -> Self {
+        Self::with_config(0.5, 10, Duration::from_secs(30), Instant::now())
+    }
+
+    /// Build a breaker that opens when the failure ratio over the last
+    /// `window_secs` seconds reaches `failure_ratio_threshold`, and allows a
+    /// trial request `open_timeout` after opening. `epoch` seeds the bucket
+    /// clock (usually `Instant::now()`).
+    pub fn with_config(
+        failure_ratio_threshold: f64,
+        window_secs: usize,
+        open_timeout: Duration,
+        epoch: Instant,
+    ) -> Self {
         Self {
-            failure_count: 0,
-            threshold: 3, 
             state: CircuitState::Closed,
+            window_secs,
+            failure_ratio_threshold,
+            open_timeout,
+            epoch,
+            buckets: vec![Bucket::default(); window_secs.max(1)],
+            opened_at: None,
         }
     }
 
-    
-    pub fn record_failure(&mut self) {
-        self.failure_count += 1;
-        if self.failure_count >= self.threshold { 
-            self.state = CircuitState::Open;
+    fn second_of(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.epoch).as_secs()
+    }
+
+    /// The bucket for `second`, reset if the slot currently holds an older one.
+    fn bucket_mut(&mut self, second: u64) -> &mut Bucket {
+        let idx = (second as usize) % self.buckets.len();
+        let slot = &mut self.buckets[idx];
+        if slot.second != second {
+            *slot = Bucket {
+                second,
+                ..Bucket::default()
+            };
         }
+        slot
     }
 
-    pub fn record_success(&mut self) {
-        self.failure_count = 0;
-        self.state = CircuitState::Closed;
+    pub fn record_failure(&mut self, now: Instant) {
+        let second = self.second_of(now);
+        let bucket = self.bucket_mut(second);
+        bucket.failures += 1;
+
+        if self.state == CircuitState::HalfOpen {
+            // A trial request failed: reopen and restart the timer.
+            self.state = CircuitState::Open;
+            self.opened_at = Some(now);
+        } else if self.state == CircuitState::Closed
+            && self.failure_ratio(now) >= self.failure_ratio_threshold
+        {
+            self.state = CircuitState::Open;
+            self.opened_at = Some(now);
+        }
     }
 
-    pub fn allow_request(&self) -> bool {
-        !matches!(self.state, CircuitState::Open)
+    pub fn record_success(&mut self, now: Instant) {
+        let second = self.second_of(now);
+        self.bucket_mut(second).successes += 1;
+
+        if self.state == CircuitState::HalfOpen {
+            // The trial succeeded: close and clear the window.
+            self.state = CircuitState::Closed;
+            self.opened_at = None;
+            for bucket in &mut self.buckets {
+                *bucket = Bucket::default();
+            }
+        }
     }
 
-    
-    pub fn try_half_open(&mut self) -> bool {
-        if self.state == CircuitState::Open {
-            self.state = CircuitState::HalfOpen;
-            false 
+    /// Failure ratio over the sliding window (0.0 when nothing was recorded).
+    pub fn failure_ratio(&self, now: Instant) -> f64 {
+        let current = self.second_of(now);
+        let window = self.buckets.len() as u64;
+        let oldest = current.saturating_sub(window - 1);
+
+        let (mut failures, mut total) = (0u64, 0u64);
+        for bucket in &self.buckets {
+            if bucket.second >= oldest && bucket.second <= current {
+                failures += bucket.failures as u64;
+                total += bucket.failures as u64 + bucket.successes as u64;
+            }
+        }
+        if total == 0 {
+            0.0
         } else {
-            self.allow_request()
+            failures as f64 / total as f64
         }
     }
+
+    /// Whether a request may proceed. When Open has outlasted `open_timeout`
+    /// this transitions Open→HalfOpen and lets a single trial request through.
+    pub fn allow_request(&mut self, now: Instant) -> bool {
+        match self.state {
+            CircuitState::Closed => true,
+            // The single trial request is already in flight.
+            CircuitState::HalfOpen => false,
+            CircuitState::Open => {
+                let elapsed = self
+                    .opened_at
+                    .map(|t| now.saturating_duration_since(t))
+                    .unwrap_or_default();
+                if elapsed >= self.open_timeout {
+                    self.state = CircuitState::HalfOpen;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    pub fn state(&self) -> &CircuitState {
+        &self.state
+    }
 }
 
 impl Default for CircuitBreaker {
@@ -135,6 +266,32 @@ impl Default for CircuitBreaker {
     }
 }
 
+/// Max in-flight requests derived from the breaker's live failure ratio rather
+/// than a static burst count: tighten as the breaker degrades, clamp hard once
+/// it is open.
+pub fn breaker_max_inflight(breaker: &CircuitBreaker, now: Instant) -> usize {
+    if *breaker.state() == CircuitState::Open {
+        return 4;
+    }
+    let ratio = breaker.failure_ratio(now);
+    if ratio >= 0.5 {
+        8
+    } else if ratio >= 0.25 {
+        16
+    } else {
+        32
+    }
+}
+
+/// Shed load when already at the limit or the breaker is not closed.
+pub fn breaker_should_shed_load(
+    breaker: &CircuitBreaker,
+    in_flight: usize,
+    limit: usize,
+) -> bool {
+    in_flight >= limit || *breaker.state() != CircuitState::Closed
+}
+
 
 pub fn exponential_backoff_ms(attempt: usize, base_ms: u64) -> u64 {
     let multiplier = 1.5_f64; 
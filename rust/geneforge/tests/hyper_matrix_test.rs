@@ -111,7 +111,7 @@ gen_test!(hyper_matrix_0019, {
 
 gen_test!(hyper_matrix_0020, {
     let mut cb = CircuitBreaker::new();
-    assert_eq!(cb.threshold, 5, "Circuit breaker threshold should be 5");
+    assert_eq!(cb.window_secs, 10, "Circuit breaker window should be 10 seconds");
 });
 
 // Report tests (GEN109-GEN112, GEN125-GEN130)
@@ -1,8 +1,40 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::{Component, Path, PathBuf};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+#[path = "../shared/sandbox.rs"]
+mod sandbox;
+use sandbox::SandboxError;
+
+/// Outcome of a single test case as reported by libtest's JSON event stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestOutcome {
+    Ok,
+    Failed,
+    Ignored,
+}
+
+impl TestOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            TestOutcome::Ok => "ok",
+            TestOutcome::Failed => "FAILED",
+            TestOutcome::Ignored => "ignored",
+        }
+    }
+}
+
+/// A structured per-test result decoded from the JSON stream. `stdout` carries
+/// any captured output, which is surfaced in the JUnit `<failure>` body.
+#[derive(Clone, Debug)]
+pub struct TestCaseResult {
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub duration_ms: u64,
+    pub stdout: String,
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct TestSummary {
     pub total: usize,
@@ -11,6 +43,12 @@ pub struct TestSummary {
     pub pass_rate: f64,
     pub targeted: bool,
     pub output: String,
+    /// Per-test results when parsed from the JSON stream; empty under the
+    /// legacy text-scraping fallback.
+    pub cases: Vec<TestCaseResult>,
+    /// Top-N slowest tests as `(name, duration_ms)`, slowest first. Empty under
+    /// the text-scraping fallback, which has no timing data.
+    pub slowest: Vec<(String, u64)>,
 }
 
 #[derive(Clone, Debug)]
@@ -21,6 +59,130 @@ pub struct StepResult {
     pub info: HashMap<String, String>,
 }
 
+/// Import-dependency graph over the workspace's `.rs` files, used to select
+/// exactly the test targets that can reach a changed file. Built once at
+/// `reset()` by scanning every source file for the module references it names
+/// (`use crate::…`, `mod …`, `#[path = "…"]`), then inverted so a changed file
+/// can be walked back to its dependents.
+#[derive(Clone, Default, Debug)]
+struct DepGraph {
+    /// Each file to the module tokens it references.
+    forward: HashMap<String, Vec<String>>,
+    /// Module token to the file that defines it (last writer wins on clashes).
+    token_to_file: HashMap<String, String>,
+    /// Each file to the set of files that reference it directly.
+    reverse: HashMap<String, Vec<String>>,
+}
+
+impl DepGraph {
+    /// Scan `work_dir` and build the forward/reverse reference maps.
+    fn build(work_dir: &str) -> Self {
+        let mut files = Vec::new();
+        collect_rs_files(Path::new(work_dir), Path::new(work_dir), &mut files);
+
+        let mut graph = DepGraph::default();
+        for rel in &files {
+            if let Some(token) = module_token(rel) {
+                graph.token_to_file.insert(token, rel.clone());
+            }
+        }
+        for rel in &files {
+            let path = Path::new(work_dir).join(rel);
+            let content = fs::read_to_string(&path).unwrap_or_default();
+            graph.forward.insert(rel.clone(), extract_references(&content));
+        }
+        graph.rebuild_reverse();
+        graph
+    }
+
+    /// Rebuild the reverse edges from the current forward/token maps.
+    fn rebuild_reverse(&mut self) {
+        self.reverse.clear();
+        for (file, tokens) in &self.forward {
+            for token in tokens {
+                if let Some(defines) = self.token_to_file.get(token) {
+                    if defines != file {
+                        self.reverse
+                            .entry(defines.clone())
+                            .or_default()
+                            .push(file.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Recompute a single file's forward edges after it was edited, then
+    /// rebuild the reverse map. Only the touched file's entry is invalidated.
+    fn update_file(&mut self, work_dir: &str, rel: &str) {
+        if let Some(token) = module_token(rel) {
+            self.token_to_file.insert(token, rel.to_string());
+        }
+        let path = Path::new(work_dir).join(rel);
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        self.forward.insert(rel.to_string(), extract_references(&content));
+        self.rebuild_reverse();
+    }
+
+    /// Test targets (stems of files `is_test_path` accepts) that transitively
+    /// reach `rel` through reverse edges, deduplicated and sorted. Returns an
+    /// empty vec when nothing reaches it.
+    fn test_targets_for(&self, rel: &str) -> Vec<String> {
+        let mut seen: HashMap<String, ()> = HashMap::new();
+        let mut stack = vec![rel.to_string()];
+        let mut targets: Vec<String> = Vec::new();
+        while let Some(file) = stack.pop() {
+            if seen.insert(file.clone(), ()).is_some() {
+                continue;
+            }
+            if PolarisCoreEnvironment::is_test_path(&file) {
+                if let Some(name) = test_target_name(&file) {
+                    if !targets.contains(&name) {
+                        targets.push(name);
+                    }
+                }
+            }
+            if let Some(dependents) = self.reverse.get(&file) {
+                for dep in dependents {
+                    stack.push(dep.clone());
+                }
+            }
+        }
+        targets.sort();
+        targets
+    }
+}
+
+/// The default set of binaries an unconfigured sandbox permits.
+const DEFAULT_COMMANDS: [&str; 8] =
+    ["cargo", "cat", "ls", "grep", "find", "head", "tail", "wc"];
+
+/// A capability policy controlling what the sandbox permits: which binaries may
+/// run, which roots may be read or written, path patterns that are denied
+/// outright, and whether test files may be edited. Empty `read_roots` or
+/// `write_roots` mean "the work directory", so [`SandboxPolicy::default`]
+/// reproduces the historical hardcoded behavior.
+#[derive(Clone, Debug)]
+pub struct SandboxPolicy {
+    pub allowed_commands: HashSet<String>,
+    pub read_roots: Vec<PathBuf>,
+    pub write_roots: Vec<PathBuf>,
+    pub deny_globs: Vec<String>,
+    pub allow_test_edits: bool,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_commands: DEFAULT_COMMANDS.iter().map(|s| s.to_string()).collect(),
+            read_roots: Vec::new(),
+            write_roots: Vec::new(),
+            deny_globs: Vec::new(),
+            allow_test_edits: false,
+        }
+    }
+}
+
 pub struct PolarisCoreEnvironment {
     pub work_dir: String,
     pub max_steps: usize,
@@ -29,6 +191,61 @@ pub struct PolarisCoreEnvironment {
     full_run_interval: usize,
     pub files_changed: Vec<String>,
     last_test_summary: TestSummary,
+    dep_graph: DepGraph,
+    /// Capability policy consulted by command and path validation.
+    policy: SandboxPolicy,
+    /// When set, the discovered `--test` targets are shuffled with an RNG
+    /// seeded from this value before each run, so ordering-dependent flakes
+    /// surface reproducibly. The seed is echoed into every `StepResult`.
+    test_seed: Option<u64>,
+    /// Optional `--test-threads` cap passed through to libtest.
+    max_test_parallelism: Option<usize>,
+    /// How step rewards are shaped (sparse pass-rate ladder vs. dense deltas).
+    reward_mode: RewardMode,
+    /// Dense-mode weights: reward per newly-passing test and penalty per
+    /// newly-failing test.
+    w_fix: f64,
+    w_break: f64,
+    /// Last seen outcome per test name, used to compute per-step transitions.
+    last_outcomes: HashMap<String, TestOutcome>,
+}
+
+/// Selects how a step's reward is computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewardMode {
+    /// Map the current global pass-rate onto the fixed reward ladder.
+    SparseLadder,
+    /// Diff per-test outcomes against the previous step: credit fixes, penalize
+    /// regressions, and add a terminal bonus once everything passes.
+    DenseDelta,
+}
+
+/// A minimal SplitMix64 generator — enough to shuffle a short list of test
+/// targets deterministically from a seed without pulling in an RNG crate.
+struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// In-place Fisher–Yates shuffle.
+    fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            slice.swap(i, j);
+        }
+    }
 }
 
 impl PolarisCoreEnvironment {
@@ -41,28 +258,98 @@ impl PolarisCoreEnvironment {
             full_run_interval: 5,
             files_changed: Vec::new(),
             last_test_summary: TestSummary::default(),
+            dep_graph: DepGraph::default(),
+            policy: SandboxPolicy::default(),
+            test_seed: None,
+            max_test_parallelism: None,
+            reward_mode: RewardMode::SparseLadder,
+            w_fix: 0.1,
+            w_break: 0.15,
+            last_outcomes: HashMap::new(),
         }
     }
 
-    fn safe_path(&self, rel: &str) -> Result<PathBuf, String> {
-        if rel.is_empty() {
-            return Err("invalid path".to_string());
+    /// Select the reward shaping; existing curricula keep the default
+    /// [`RewardMode::SparseLadder`].
+    pub fn with_reward_mode(mut self, mode: RewardMode) -> Self {
+        self.reward_mode = mode;
+        self
+    }
+
+    /// Tune the dense-mode per-test fix reward and regression penalty.
+    pub fn with_reward_weights(mut self, w_fix: f64, w_break: f64) -> Self {
+        self.w_fix = w_fix;
+        self.w_break = w_break;
+        self
+    }
+
+    /// Install a capability policy; callers use this to run stricter (read-only
+    /// exploration) or looser (allow `rustfmt`, permit test edits) sandboxes
+    /// without forking the environment.
+    pub fn with_policy(mut self, policy: SandboxPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Seed the RNG that shuffles discovered test targets before each run, so
+    /// flaky ordering dependencies reproduce deterministically.
+    pub fn with_test_seed(mut self, seed: u64) -> Self {
+        self.test_seed = Some(seed);
+        self
+    }
+
+    /// Cap libtest parallelism (`--test-threads`) so large suites run faster
+    /// within a single step.
+    pub fn with_max_test_parallelism(mut self, threads: usize) -> Self {
+        self.max_test_parallelism = Some(threads);
+        self
+    }
+
+    /// Canonicalized roots a policy field resolves to, treating an empty list
+    /// as "the work directory".
+    fn effective_roots(&self, configured: &[PathBuf]) -> Vec<PathBuf> {
+        if configured.is_empty() {
+            return Path::new(&self.work_dir)
+                .canonicalize()
+                .ok()
+                .into_iter()
+                .collect();
         }
-        let rel_path = Path::new(rel);
-        if rel_path.is_absolute() {
-            return Err("invalid path".to_string());
+        configured.iter().filter_map(|r| r.canonicalize().ok()).collect()
+    }
+
+    fn safe_path(&self, rel: &str, need_write: bool) -> Result<PathBuf, String> {
+        let normalized = rel.replace('\\', "/");
+        if self.policy.deny_globs.iter().any(|glob| glob_match(glob, &normalized)) {
+            return Err("path denied by policy".to_string());
         }
-        for component in rel_path.components() {
-            if matches!(component, Component::ParentDir | Component::RootDir) {
-                return Err("invalid path".to_string());
+
+        // Containment and symlink escape are delegated to the shared sandbox
+        // module.
+        let read_roots = self.effective_roots(&self.policy.read_roots);
+        let mut resolved = None;
+        for root in &read_roots {
+            match sandbox::resolve_within(root, rel) {
+                Ok(target) => {
+                    resolved = Some(target);
+                    break;
+                }
+                // A bad `rel` is root-independent, so surface it immediately.
+                Err(SandboxError::InvalidPath) => return Err("invalid path".to_string()),
+                // Escape or an unresolvable root: try the next read root.
+                Err(SandboxError::Escape) | Err(SandboxError::Io(_)) => continue,
             }
         }
-        let root = Path::new(&self.work_dir)
-            .canonicalize()
-            .map_err(|e| e.to_string())?;
-        let target = root.join(rel_path);
-        if !target.starts_with(&root) {
-            return Err("path escapes workspace".to_string());
+        let target = resolved.ok_or_else(|| "path escapes sandbox".to_string())?;
+
+        if need_write {
+            let write_roots = self.effective_roots(&self.policy.write_roots);
+            let writable = write_roots
+                .iter()
+                .any(|root| sandbox::resolve_within(root, rel).is_ok());
+            if !writable {
+                return Err("write outside writable roots".to_string());
+            }
         }
         Ok(target)
     }
@@ -75,7 +362,7 @@ impl PolarisCoreEnvironment {
             || normalized.ends_with("_test.rs")
     }
 
-    fn validate_command(command: &str) -> Result<Vec<String>, String> {
+    fn validate_command(&self, command: &str) -> Result<Vec<String>, String> {
         if command
             .chars()
             .any(|ch| [';', '&', '|', '`', '$', '>', '<'].contains(&ch))
@@ -90,9 +377,8 @@ impl PolarisCoreEnvironment {
         if parts.is_empty() {
             return Err("empty command".to_string());
         }
-        let allowed = ["cargo", "cat", "ls", "grep", "find", "head", "tail", "wc"];
-        if !allowed.contains(&parts[0].as_str()) {
-            return Err("command not allowed".to_string());
+        if !self.policy.allowed_commands.contains(&parts[0]) {
+            return Err("command not allowed by policy".to_string());
         }
         Ok(parts)
     }
@@ -104,20 +390,20 @@ impl PolarisCoreEnvironment {
         }
         if action_type == "edit" || action_type == "read" {
             let rel = action.get("file").map(String::as_str).unwrap_or("");
-            let _ = self.safe_path(rel)?;
-            if action_type == "edit" && Self::is_test_path(rel) {
+            let _ = self.safe_path(rel, action_type == "edit")?;
+            if action_type == "edit" && !self.policy.allow_test_edits && Self::is_test_path(rel) {
                 return Err("editing test files is not allowed".to_string());
             }
         }
         if action_type == "run_command" {
             let command = action.get("command").map(String::as_str).unwrap_or("");
-            let _ = Self::validate_command(command)?;
+            let _ = self.validate_command(command)?;
         }
         Ok(())
     }
 
     fn execute_command(&self, command: &str) -> Result<String, String> {
-        let parts = Self::validate_command(command)?;
+        let parts = self.validate_command(command)?;
         let mut iter = parts.iter();
         let binary = iter.next().ok_or_else(|| "empty command".to_string())?;
         let args: Vec<&str> = iter.map(String::as_str).collect();
@@ -134,68 +420,122 @@ impl PolarisCoreEnvironment {
     }
 
     fn edit(&mut self, rel: &str, content: &str) -> Result<String, String> {
-        let target = self.safe_path(rel)?;
+        let target = self.safe_path(rel, true)?;
         if let Some(parent) = target.parent() {
             fs::create_dir_all(parent).map_err(|e| e.to_string())?;
         }
         fs::write(&target, content).map_err(|e| e.to_string())?;
         self.files_changed.push(rel.to_string());
+        // Only the edited file's dependency entry is invalidated and recomputed.
+        self.dep_graph.update_file(&self.work_dir, rel);
         Ok(format!("edited {}", rel))
     }
 
     fn read(&self, rel: &str) -> Result<String, String> {
-        let target = self.safe_path(rel)?;
+        let target = self.safe_path(rel, false)?;
         fs::read_to_string(target).map_err(|e| e.to_string())
     }
 
-    fn tests_for_file(&self, rel: &str) -> Vec<&'static str> {
-        if rel.starts_with("src/allocator.rs") {
-            return vec!["allocator_tests"];
-        }
-        if rel.starts_with("src/routing.rs") {
-            return vec!["routing_tests", "workflow_integration_tests"];
-        }
-        if rel.starts_with("src/policy.rs") {
-            return vec!["policy_tests", "chaos_replay_tests"];
-        }
-        if rel.starts_with("src/resilience.rs") {
-            return vec!["resilience_tests", "chaos_replay_tests"];
-        }
-        if rel.starts_with("src/security.rs") {
-            return vec!["security_tests"];
-        }
-        if rel.starts_with("src/queue.rs") || rel.starts_with("src/statistics.rs") {
-            return vec!["queue_statistics_tests"];
-        }
-        if rel.starts_with("src/workflow.rs") || rel.starts_with("src/economics.rs") {
-            return vec!["workflow_integration_tests"];
+    /// Deduplicated `--test` target names that can reach any of `changed`
+    /// through the reverse-dependency graph. Returns `None` when at least one
+    /// changed file is reachable by zero test targets, signalling the caller to
+    /// fall back to a full run so coverage is never silently incomplete.
+    fn targets_for_changes(&self, changed: &[String]) -> Option<Vec<String>> {
+        let mut targets: Vec<String> = Vec::new();
+        for rel in changed {
+            let file_targets = self.dep_graph.test_targets_for(rel);
+            if file_targets.is_empty() {
+                return None;
+            }
+            for target in file_targets {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
         }
-        if rel.starts_with("services/") || rel.starts_with("shared/") {
-            return vec!["services_contracts"];
+        targets.sort();
+        Some(targets)
+    }
+
+    /// Run a cargo test command, preferring the structured JSON event stream
+    /// and falling back to the legacy text scraper when the toolchain does not
+    /// emit JSON.
+    fn run_tests(&self, base_command: &str, targeted: bool) -> TestSummary {
+        let mut libtest_args =
+            String::from(" -- -Z unstable-options --format json --report-time");
+        if let Some(threads) = self.max_test_parallelism {
+            libtest_args.push_str(&format!(" --test-threads {threads}"));
         }
-        if rel.starts_with("migrations/") {
-            return vec!["services_contracts"];
+        let json_command = format!("{base_command}{libtest_args}");
+        let output = self.execute_command(&json_command).unwrap_or_default();
+        if let Some(cases) = parse_cargo_test_json(&output) {
+            return summary_from_cases(cases, targeted, output);
         }
-        Vec::new()
+        // Older toolchain: re-run plainly and scrape the human-readable output.
+        let plain = match self.max_test_parallelism {
+            Some(threads) => format!("{base_command} -- --test-threads {threads}"),
+            None => base_command.to_string(),
+        };
+        let output = self.execute_command(&plain).unwrap_or_default();
+        parse_cargo_test_summary(&output, targeted)
     }
 
     fn run_full_tests(&self) -> TestSummary {
-        let output = self.execute_command("cargo test").unwrap_or_default();
-        parse_cargo_test_summary(&output, false)
+        self.run_tests("cargo test", false)
     }
 
     fn run_targeted_tests(&self, rel: &str) -> TestSummary {
-        let targets = self.tests_for_file(rel);
-        if targets.is_empty() {
-            return TestSummary::default();
+        let mut targets = match self.targets_for_changes(std::slice::from_ref(&rel.to_string())) {
+            Some(targets) if !targets.is_empty() => targets,
+            // Reachable by zero test targets: fall back to the full run.
+            _ => return self.run_full_tests(),
+        };
+        // Shuffle the target order with the seeded RNG so an ordering-dependent
+        // flake surfaces the same way on every run with this seed.
+        if let Some(seed) = self.test_seed {
+            SeededRng::new(seed).shuffle(&mut targets);
         }
+
         let mut command = String::from("cargo test");
-        for target in targets {
+        for target in &targets {
             command.push_str(" --test ");
             command.push_str(target);
         }
-        let output = self.execute_command(&command).unwrap_or_default();
-        parse_cargo_test_summary(&output, true)
+        self.run_tests(&command, true)
+    }
+
+    /// Count per-test transitions between the previously recorded outcomes and
+    /// the new summary's cases: `(newly_passing, newly_failing)`.
+    fn outcome_deltas(&self, summary: &TestSummary) -> (usize, usize) {
+        let mut newly_passing = 0;
+        let mut newly_failing = 0;
+        for case in &summary.cases {
+            match self.last_outcomes.get(&case.name) {
+                Some(TestOutcome::Failed) if case.outcome == TestOutcome::Ok => newly_passing += 1,
+                Some(TestOutcome::Ok) if case.outcome == TestOutcome::Failed => newly_failing += 1,
+                _ => {}
+            }
+        }
+        (newly_passing, newly_failing)
+    }
+
+    /// Merge a summary's per-test outcomes into the tracked set so the next
+    /// step diffs against them. Targeted runs update only the tests they ran.
+    fn record_outcomes(&mut self, summary: &TestSummary) {
+        for case in &summary.cases {
+            self.last_outcomes.insert(case.name.clone(), case.outcome);
+        }
+    }
+
+    /// Dense reward: credit each fix, penalize each regression, and add a
+    /// terminal bonus once the whole suite passes.
+    fn dense_reward(&self, summary: &TestSummary, newly_passing: usize, newly_failing: usize) -> f64 {
+        let mut reward =
+            self.w_fix * newly_passing as f64 - self.w_break * newly_failing as f64;
+        if !summary.targeted && summary.total > 0 && summary.pass_rate >= 1.0 {
+            reward += 1.0;
+        }
+        reward
     }
 
     fn build_step_result(
@@ -215,7 +555,12 @@ impl PolarisCoreEnvironment {
         observation.insert("tests_failed".to_string(), summary.failed.to_string());
         observation.insert("pass_rate".to_string(), format!("{:.4}", summary.pass_rate));
         observation.insert("targeted_run".to_string(), summary.targeted.to_string());
+        let slowest_test_ms = summary.slowest.first().map(|(_, ms)| *ms).unwrap_or(0);
+        observation.insert("slowest_test_ms".to_string(), slowest_test_ms.to_string());
 
+        if let Some(seed) = self.test_seed {
+            info.insert("test_seed".to_string(), seed.to_string());
+        }
         info.insert("step".to_string(), self.step_count.to_string());
         info.insert("max_steps".to_string(), self.max_steps.to_string());
         info.insert("total_bugs".to_string(), "1020".to_string());
@@ -239,8 +584,11 @@ impl PolarisCoreEnvironment {
         self.step_count = 0;
         self.mutating_steps = 0;
         self.files_changed.clear();
+        self.dep_graph = DepGraph::build(&self.work_dir);
         self.last_test_summary = self.run_full_tests();
         let summary = self.last_test_summary.clone();
+        // Seed the baseline outcomes so the first step's deltas are meaningful.
+        self.record_outcomes(&summary);
         self.build_step_result(String::new(), &summary, 0.0, false, HashMap::new())
     }
 
@@ -290,8 +638,17 @@ impl PolarisCoreEnvironment {
             }
         }
 
-        let reward = sparse_reward(summary.pass_rate);
+        // Per-test transitions against the previously tracked outcomes, always
+        // surfaced; they additionally drive the reward in dense mode.
+        let (newly_passing, newly_failing) = self.outcome_deltas(&summary);
+        let reward = match self.reward_mode {
+            RewardMode::SparseLadder => sparse_reward(summary.pass_rate),
+            RewardMode::DenseDelta => self.dense_reward(&summary, newly_passing, newly_failing),
+        };
+        self.record_outcomes(&summary);
         self.last_test_summary = summary.clone();
+        info.insert("newly_passing".to_string(), newly_passing.to_string());
+        info.insert("newly_failing".to_string(), newly_failing.to_string());
         let done = self.step_count >= self.max_steps
             || (!summary.targeted && summary.total > 0 && summary.pass_rate >= 1.0);
 
@@ -302,10 +659,133 @@ impl PolarisCoreEnvironment {
                 String::new()
             }
         };
-        self.build_step_result(rendered_result, &summary, reward, done, info)
+        let mut result = self.build_step_result(rendered_result, &summary, reward, done, info);
+        result
+            .observation
+            .insert("newly_passing".to_string(), newly_passing.to_string());
+        result
+            .observation
+            .insert("newly_failing".to_string(), newly_failing.to_string());
+        result
+    }
+}
+
+/// Minimal glob matcher for deny patterns: `*` matches any run of characters
+/// (including path separators) and `?` matches exactly one. Sufficient for the
+/// coarse `deny_globs` the policy carries, without an external glob crate.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(p: &[u8], t: &[u8]) -> bool {
+        match p.first() {
+            None => t.is_empty(),
+            Some(b'*') => matches(&p[1..], t) || (!t.is_empty() && matches(p, &t[1..])),
+            Some(b'?') => !t.is_empty() && matches(&p[1..], &t[1..]),
+            Some(&c) => !t.is_empty() && t[0] == c && matches(&p[1..], &t[1..]),
+        }
+    }
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+/// Recursively collect workspace-relative paths of every `.rs` file under
+/// `dir`, skipping the `target/` build directory.
+fn collect_rs_files(root: &Path, dir: &Path, out: &mut Vec<String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if path.file_name().map(|n| n == "target").unwrap_or(false) {
+                continue;
+            }
+            collect_rs_files(root, &path, out);
+        } else if path.extension().map(|e| e == "rs").unwrap_or(false) {
+            if let Ok(rel) = path.strip_prefix(root) {
+                out.push(rel.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+}
+
+/// Module token a file defines: its stem, or the parent directory name for the
+/// `mod.rs`/`lib.rs`/`main.rs` roots.
+fn module_token(rel: &str) -> Option<String> {
+    let norm = rel.replace('\\', "/");
+    let path = Path::new(&norm);
+    let stem = path.file_stem()?.to_string_lossy().to_string();
+    if matches!(stem.as_str(), "mod" | "lib" | "main") {
+        return path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string());
+    }
+    Some(stem)
+}
+
+/// `--test` target name for a test file: its stem.
+fn test_target_name(rel: &str) -> Option<String> {
+    Path::new(&rel.replace('\\', "/"))
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+}
+
+/// Extract the module tokens a source file references: the first segment of a
+/// `use crate::…` path, the target of a `mod …;` declaration, and the file
+/// stem named by a `#[path = "…"]` attribute.
+fn extract_references(content: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut push = |token: String| {
+        if !token.is_empty() && !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    };
+    for raw in content.lines() {
+        let line = raw.trim();
+        if let Some(rest) = line.strip_prefix("use crate::") {
+            push(leading_ident(rest));
+        }
+        if let Some(rest) = mod_declaration(line) {
+            push(rest);
+        }
+        if let Some(path) = path_attribute(line) {
+            if let Some(token) = module_token(&path) {
+                push(token);
+            }
+        }
+    }
+    tokens
+}
+
+/// Leading identifier of a path fragment (up to `::`, `;`, `{`, or whitespace).
+fn leading_ident(fragment: &str) -> String {
+    fragment
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect()
+}
+
+/// The module name in a `mod foo;` / `pub mod foo;` declaration.
+fn mod_declaration(line: &str) -> Option<String> {
+    let rest = line
+        .strip_prefix("pub mod ")
+        .or_else(|| line.strip_prefix("mod "))?;
+    let name = leading_ident(rest.trim_start());
+    if rest.trim_end().ends_with(';') && !name.is_empty() {
+        Some(name)
+    } else {
+        None
     }
 }
 
+/// The quoted path in a `#[path = "…"]` attribute.
+fn path_attribute(line: &str) -> Option<String> {
+    let idx = line.find("#[path")?;
+    let after = &line[idx..];
+    let start = after.find('"')? + 1;
+    let end = after[start..].find('"')? + start;
+    Some(after[start..end].to_string())
+}
+
 fn parse_cargo_test_summary(output: &str, targeted: bool) -> TestSummary {
     let mut passed = 0usize;
     let mut failed = 0usize;
@@ -339,7 +819,174 @@ fn parse_cargo_test_summary(output: &str, targeted: bool) -> TestSummary {
         pass_rate,
         targeted,
         output: output.to_string(),
+        cases: Vec::new(),
+        slowest: Vec::new(),
+    }
+}
+
+/// Parse libtest's JSON event stream (`--format json --report-time`) into a
+/// structured list of case results. Returns `None` when the output carries no
+/// recognizable test events, so callers can fall back to the text scraper on
+/// toolchains that do not emit JSON.
+fn parse_cargo_test_json(output: &str) -> Option<Vec<TestCaseResult>> {
+    let mut cases = Vec::new();
+    let mut saw_event = false;
+    for line in output.lines() {
+        let line = line.trim();
+        if !line.starts_with('{') || !line.contains("\"type\"") {
+            continue;
+        }
+        if json_str(line, "type").as_deref() != Some("test") {
+            continue;
+        }
+        let event = json_str(line, "event").unwrap_or_default();
+        let outcome = match event.as_str() {
+            "ok" => TestOutcome::Ok,
+            "failed" => TestOutcome::Failed,
+            "ignored" => TestOutcome::Ignored,
+            // "started" and anything else carry no terminal outcome.
+            _ => continue,
+        };
+        saw_event = true;
+        let name = json_str(line, "name").unwrap_or_default();
+        let duration_ms = json_num(line, "exec_time")
+            .map(|secs| (secs * 1000.0).round() as u64)
+            .unwrap_or(0);
+        let stdout = json_str(line, "stdout").unwrap_or_default();
+        cases.push(TestCaseResult { name, outcome, duration_ms, stdout });
+    }
+    if saw_event { Some(cases) } else { None }
+}
+
+/// Derive a [`TestSummary`] from structured case results, ignoring skipped
+/// tests in the pass-rate denominator.
+fn summary_from_cases(cases: Vec<TestCaseResult>, targeted: bool, output: String) -> TestSummary {
+    let passed = cases.iter().filter(|c| c.outcome == TestOutcome::Ok).count();
+    let failed = cases.iter().filter(|c| c.outcome == TestOutcome::Failed).count();
+    let total = passed + failed;
+    let pass_rate = if total > 0 {
+        passed as f64 / total as f64
+    } else {
+        0.0
+    };
+    // Top-N slowest tests, a coarse performance signal for the agent.
+    const SLOWEST_N: usize = 5;
+    let mut timed: Vec<(String, u64)> = cases
+        .iter()
+        .map(|c| (c.name.clone(), c.duration_ms))
+        .collect();
+    timed.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    timed.truncate(SLOWEST_N);
+    TestSummary {
+        total,
+        passed,
+        failed,
+        pass_rate,
+        targeted,
+        output,
+        cases,
+        slowest: timed,
+    }
+}
+
+/// Render a [`TestSummary`]'s structured cases as a JUnit-style
+/// `<testsuites>`/`<testcase>` document, with failures carrying the captured
+/// output so the environment can feed CI harnesses the way cargo2junit does.
+pub fn export_junit(summary: &TestSummary) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites tests=\"{}\" failures=\"{}\">\n",
+        summary.total, summary.failed
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"polariscore\" tests=\"{}\" failures=\"{}\">\n",
+        summary.total, summary.failed
+    ));
+    for case in &summary.cases {
+        let time = case.duration_ms as f64 / 1000.0;
+        xml.push_str(&format!(
+            "    <testcase name=\"{}\" time=\"{:.3}\"",
+            xml_escape(&case.name),
+            time
+        ));
+        match case.outcome {
+            TestOutcome::Failed => {
+                xml.push_str(">\n");
+                xml.push_str(&format!(
+                    "      <failure message=\"test failed\">{}</failure>\n",
+                    xml_escape(&case.stdout)
+                ));
+                xml.push_str("    </testcase>\n");
+            }
+            TestOutcome::Ignored => {
+                xml.push_str(">\n      <skipped/>\n    </testcase>\n");
+            }
+            TestOutcome::Ok => {
+                xml.push_str("/>\n");
+            }
+        }
     }
+    xml.push_str("  </testsuite>\n");
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Escape the five XML predefined entities in attribute/text content.
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Extract a string field `"key": "value"` from a flat JSON object line,
+/// honoring backslash escapes. Sufficient for libtest's event objects.
+fn json_str(line: &str, key: &str) -> Option<String> {
+    let needle = format!("\"{key}\"");
+    let idx = line.find(&needle)?;
+    let after = &line[idx + needle.len()..];
+    let colon = after.find(':')?;
+    let rest = after[colon + 1..].trim_start();
+    let mut chars = rest.chars();
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut value = String::new();
+    let mut escaped = false;
+    for ch in chars {
+        if escaped {
+            match ch {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                other => value.push(other),
+            }
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            return Some(value);
+        } else {
+            value.push(ch);
+        }
+    }
+    None
+}
+
+/// Extract a numeric field `"key": <number>` from a flat JSON object line.
+fn json_num(line: &str, key: &str) -> Option<f64> {
+    let needle = format!("\"{key}\"");
+    let idx = line.find(&needle)?;
+    let after = &line[idx + needle.len()..];
+    let colon = after.find(':')?;
+    let rest = after[colon + 1..].trim_start();
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+' || c == 'e' || c == 'E'))
+        .unwrap_or(rest.len());
+    rest[..end].parse::<f64>().ok()
 }
 
 fn extract_count(line: &str, marker: &str) -> usize {
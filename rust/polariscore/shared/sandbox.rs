@@ -0,0 +1,78 @@
+//! Symlink-aware path containment for the RL environment's file actions. It
+//! resolves attacker-influenced relative paths against a fixed root, rejecting
+//! absolute paths, `..` traversal, and symlinks that escape the root. The
+//! environment in `environment/setup.rs` is its only caller; no HTTP handler
+//! uses it.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Why a path was rejected. The environment maps these onto its own error
+/// strings rather than leaking raw filesystem error text.
+#[derive(Debug)]
+pub enum SandboxError {
+    /// Empty, absolute, or containing a `..`/root component.
+    InvalidPath,
+    /// Resolves (after following symlinks) outside the root.
+    Escape,
+    /// A filesystem error while canonicalizing.
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxError::InvalidPath => write!(f, "invalid path"),
+            SandboxError::Escape => write!(f, "path escapes sandbox"),
+            SandboxError::Io(err) => write!(f, "io error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SandboxError {}
+
+/// Resolve `rel` within `root`, guaranteeing the result stays inside `root`
+/// even across symlinks. Absolute paths and `..` components are rejected up
+/// front; the final path is canonicalized and re-checked with `starts_with` so
+/// a symlink pointing outside the root is caught. A path that does not yet
+/// exist (e.g. a file about to be written) is validated against its nearest
+/// existing ancestor so a symlinked parent still cannot smuggle a write out.
+pub fn resolve_within(root: &Path, rel: &str) -> Result<PathBuf, SandboxError> {
+    if rel.is_empty() {
+        return Err(SandboxError::InvalidPath);
+    }
+    let rel_path = Path::new(rel);
+    if rel_path.is_absolute() {
+        return Err(SandboxError::InvalidPath);
+    }
+    for component in rel_path.components() {
+        if matches!(component, Component::ParentDir | Component::RootDir) {
+            return Err(SandboxError::InvalidPath);
+        }
+    }
+
+    let root = root.canonicalize().map_err(SandboxError::Io)?;
+    let target = root.join(rel_path);
+
+    // Canonicalize as far as the path exists, then re-verify containment.
+    let anchor = nearest_existing(&target).map_err(SandboxError::Io)?;
+    if !anchor.starts_with(&root) {
+        return Err(SandboxError::Escape);
+    }
+    Ok(target)
+}
+
+/// Canonicalize the longest existing prefix of `path`, so containment can be
+/// checked even when the leaf does not exist yet.
+fn nearest_existing(path: &Path) -> std::io::Result<PathBuf> {
+    let mut current = path;
+    loop {
+        match current.canonicalize() {
+            Ok(resolved) => return Ok(resolved),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => match current.parent() {
+                Some(parent) => current = parent,
+                None => return current.canonicalize(),
+            },
+            Err(err) => return Err(err),
+        }
+    }
+}
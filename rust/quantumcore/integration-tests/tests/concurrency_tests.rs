@@ -1,6 +1,6 @@
 //! Concurrency tests for QuantumCore
 //!
-//! Tests cover: B1-B12 concurrency bugs
+//! Tests cover: B1-B13 concurrency bugs
 
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, AtomicBool, Ordering};
@@ -24,6 +24,222 @@ fn read_source(relative_path: &str) -> String {
         .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e))
 }
 
+// =============================================================================
+// Static lock-order analysis
+// =============================================================================
+//
+// A substring scan for `order_book` vs `risk_state` misreads comments, renamed
+// locals and reordered reads. This module parses the source with `syn`, records
+// the ordered lock acquisitions held simultaneously in each function, builds a
+// global "acquired-before" graph, and reports the exact cycle when two
+// functions disagree on ordering. It extends to any future lock-order scenario
+// without touching the tests.
+mod lock_order {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use syn::visit::Visit;
+    use syn::{Expr, Item, Local, Pat, Stmt};
+
+    /// The lock-guard methods we treat as an acquisition.
+    const ACQUIRE: [&str; 3] = ["lock", "read", "write"];
+
+    /// Canonical name of the locked resource behind `recv.lock()` — the field
+    /// or variable the guard is taken on (`self.order_books` -> `order_books`).
+    fn receiver_token(expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Field(f) => match &f.member {
+                syn::Member::Named(id) => Some(id.to_string()),
+                syn::Member::Unnamed(_) => None,
+            },
+            Expr::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+            Expr::MethodCall(m) => receiver_token(&m.receiver),
+            Expr::Reference(r) => receiver_token(&r.expr),
+            Expr::Paren(p) => receiver_token(&p.expr),
+            _ => None,
+        }
+    }
+
+    /// First lock acquisition reachable in `expr`, as (guard-resource token).
+    fn acquisition(expr: &Expr) -> Option<String> {
+        if let Expr::MethodCall(m) = expr {
+            if ACQUIRE.contains(&m.method.to_string().as_str()) {
+                if let Some(tok) = receiver_token(&m.receiver) {
+                    return Some(tok);
+                }
+            }
+            return acquisition(&m.receiver);
+        }
+        None
+    }
+
+    fn binding_ident(pat: &Pat) -> Option<String> {
+        match pat {
+            Pat::Ident(p) => Some(p.ident.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Walk one function body in order, tracking the set of guards currently
+    /// held (honouring explicit `drop(..)`), and emit an edge from every
+    /// held resource to each newly acquired one.
+    struct FnScanner {
+        held: Vec<(String, String)>, // (guard ident, resource token)
+        edges: BTreeSet<(String, String)>,
+    }
+
+    impl FnScanner {
+        fn new() -> Self {
+            Self {
+                held: Vec::new(),
+                edges: BTreeSet::new(),
+            }
+        }
+
+        fn acquire(&mut self, guard: String, token: String) {
+            for (_, prior) in &self.held {
+                if prior != &token {
+                    self.edges.insert((prior.clone(), token.clone()));
+                }
+            }
+            self.held.push((guard, token));
+        }
+
+        fn release(&mut self, guard: &str) {
+            self.held.retain(|(g, _)| g != guard);
+        }
+
+        fn scan_block(&mut self, block: &syn::Block) {
+            for stmt in &block.stmts {
+                self.scan_stmt(stmt);
+            }
+        }
+
+        fn scan_stmt(&mut self, stmt: &Stmt) {
+            match stmt {
+                Stmt::Local(Local {
+                    pat,
+                    init: Some(init),
+                    ..
+                }) => {
+                    if let Some(token) = acquisition(&init.expr) {
+                        let guard = binding_ident(pat).unwrap_or_else(|| token.clone());
+                        self.acquire(guard, token);
+                    }
+                }
+                Stmt::Expr(Expr::Call(call), _) => {
+                    // drop(x) releases guard x.
+                    if let Expr::Path(p) = &*call.func {
+                        if p.path.is_ident("drop") {
+                            if let Some(Expr::Path(arg)) = call.args.first() {
+                                if let Some(id) = arg.path.get_ident() {
+                                    self.release(&id.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Collects edges across every function in a parsed file.
+    struct FileScanner {
+        edges: BTreeSet<(String, String)>,
+    }
+
+    impl<'ast> Visit<'ast> for FileScanner {
+        fn visit_item_fn(&mut self, f: &'ast syn::ItemFn) {
+            let mut s = FnScanner::new();
+            s.scan_block(&f.block);
+            self.edges.extend(s.edges);
+        }
+
+        fn visit_impl_item_fn(&mut self, f: &'ast syn::ImplItemFn) {
+            let mut s = FnScanner::new();
+            s.scan_block(&f.block);
+            self.edges.extend(s.edges);
+        }
+    }
+
+    /// The acquired-before graph for a source file.
+    pub struct LockGraph {
+        adj: BTreeMap<String, BTreeSet<String>>,
+    }
+
+    impl LockGraph {
+        pub fn from_source(src: &str) -> Self {
+            let file = syn::parse_file(src).expect("engine.rs should parse as Rust");
+            let mut scanner = FileScanner {
+                edges: BTreeSet::new(),
+            };
+            for item in &file.items {
+                if let Item::Impl(_) | Item::Fn(_) = item {
+                    scanner.visit_item(item);
+                }
+            }
+            let mut adj: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+            for (from, to) in scanner.edges {
+                adj.entry(from).or_default().insert(to);
+            }
+            Self { adj }
+        }
+
+        /// Return a concrete acquired-before cycle (e.g.
+        /// `order_books -> risk -> order_books`) if the graph has one.
+        pub fn find_cycle(&self) -> Option<Vec<String>> {
+            #[derive(Clone, Copy, PartialEq)]
+            enum Mark {
+                Open,
+                Done,
+            }
+            let mut state: BTreeMap<&str, Mark> = BTreeMap::new();
+            let mut stack: Vec<String> = Vec::new();
+
+            fn dfs<'a>(
+                node: &'a str,
+                adj: &'a BTreeMap<String, BTreeSet<String>>,
+                state: &mut BTreeMap<&'a str, Mark>,
+                stack: &mut Vec<String>,
+            ) -> Option<Vec<String>> {
+                state.insert(node, Mark::Open);
+                stack.push(node.to_string());
+                if let Some(succs) = adj.get(node) {
+                    for next in succs {
+                        match state.get(next.as_str()) {
+                            Some(Mark::Open) => {
+                                // Back edge: close the loop for a readable report.
+                                let start = stack.iter().position(|n| n == next).unwrap();
+                                let mut cycle = stack[start..].to_vec();
+                                cycle.push(next.clone());
+                                return Some(cycle);
+                            }
+                            Some(Mark::Done) => {}
+                            None => {
+                                if let Some(c) = dfs(next, adj, state, stack) {
+                                    return Some(c);
+                                }
+                            }
+                        }
+                    }
+                }
+                stack.pop();
+                state.insert(node, Mark::Done);
+                None
+            }
+
+            for node in self.adj.keys() {
+                if state.get(node.as_str()).is_none() {
+                    if let Some(cycle) = dfs(node, &self.adj, &mut state, &mut stack) {
+                        return Some(cycle);
+                    }
+                }
+            }
+            None
+        }
+    }
+}
+
 // =============================================================================
 // B1: Lock Ordering Deadlock Tests
 // =============================================================================
@@ -69,26 +285,17 @@ fn test_b1_lock_ordering_in_source() {
     // update_risk_and_cancel: risk_state -> order_book
     let src = read_source("services/matching/src/engine.rs");
 
-    // Find lock acquisition order in submit_order
-    let submit_fn = src.split("fn submit_order").nth(1).unwrap_or("");
-    let submit_body = submit_fn.split("\n    pub ").next().unwrap_or(submit_fn);
-
-    // Find lock acquisition order in update_risk_and_cancel
-    let cancel_fn = src.split("fn update_risk_and_cancel").nth(1).unwrap_or("");
-    let cancel_body = cancel_fn.split("\n    pub ").next().unwrap_or(cancel_fn);
-
-    // In submit_order, order_books is locked before risk_state
-    let submit_books_first = submit_body.find("order_book").unwrap_or(usize::MAX)
-        < submit_body.find("risk_state").unwrap_or(usize::MAX);
-
-    // In update_risk_and_cancel, risk_state is locked before order_books
-    let cancel_risk_first = cancel_body.find("risk_state").unwrap_or(usize::MAX)
-        < cancel_body.find("order_book").unwrap_or(usize::MAX);
-
-    // If both acquire in different orders, deadlock is possible
-    assert!(!(submit_books_first && cancel_risk_first),
-        "Lock ordering is inconsistent: submit_order locks order_book->risk, \
-         but update_risk_and_cancel locks risk->order_book — DEADLOCK possible (bug B1)");
+    // Build the acquired-before graph from every function and check it is
+    // acyclic. A cycle means two functions acquire the same pair of locks in
+    // opposite orders, which can deadlock.
+    let graph = lock_order::LockGraph::from_source(&src);
+    if let Some(cycle) = graph.find_cycle() {
+        panic!(
+            "Lock ordering is inconsistent — acquired-before cycle {} means a \
+             deadlock is possible (bug B1)",
+            cycle.join(" -> ")
+        );
+    }
 }
 
 // =============================================================================
@@ -326,6 +533,108 @@ fn test_b6_bounded_channel() {
     assert!(tx.try_send(99).is_ok(), "Channel has room");
 }
 
+// =============================================================================
+// B6b: Channel Multiplexing (select! vs try_recv spin) Tests
+// =============================================================================
+
+#[test]
+fn test_multiplexer_uses_select_not_spin_in_source() {
+    // The consumer loop must block on all sources with select!, not busy-poll
+    // each with try_recv (CPU spin + source starvation).
+    let src = read_source("services/matching/src/multiplexer.rs");
+    let run_fn = src.split("pub fn run").nth(1).unwrap_or("");
+    // The loop body ends at the next top-level item. Comment lines are skipped
+    // so a note naming the fix cannot satisfy the check.
+    let run_body: String = run_fn
+        .split("\n}")
+        .next()
+        .unwrap_or(run_fn)
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("//"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert!(
+        !run_body.contains("try_recv"),
+        "multiplexer::run busy-polls with try_recv — block on all sources with \
+         crossbeam::channel::select! instead"
+    );
+    assert!(
+        run_body.contains("select!"),
+        "multiplexer::run must multiplex its sources with \
+         crossbeam::channel::select!"
+    );
+}
+
+#[test]
+fn test_multiplexer_shutdown_is_prompt_and_fair() {
+    use crossbeam::channel::{bounded, select, unbounded};
+
+    let (order_tx, order_rx) = unbounded::<u64>();
+    let (cancel_tx, cancel_rx) = unbounded::<u64>();
+    let (tick_tx, tick_rx) = unbounded::<u64>();
+    let (shutdown_tx, shutdown_rx) = bounded::<()>(1);
+
+    // A correct select!-based consumer: blocks on all sources, honours shutdown.
+    let consumer = thread::spawn(move || {
+        let mut seen = (0u64, 0u64, 0u64);
+        loop {
+            select! {
+                recv(order_rx) -> m => if m.is_ok() { seen.0 += 1; },
+                recv(cancel_rx) -> m => if m.is_ok() { seen.1 += 1; },
+                recv(tick_rx) -> m => if m.is_ok() { seen.2 += 1; },
+                recv(shutdown_rx) -> _ => return seen,
+            }
+        }
+    });
+
+    // Feed each source so none is starved.
+    for i in 0..50 {
+        order_tx.send(i).unwrap();
+        cancel_tx.send(i).unwrap();
+        tick_tx.send(i).unwrap();
+    }
+    thread::sleep(Duration::from_millis(20));
+    shutdown_tx.send(()).unwrap();
+
+    let start = Instant::now();
+    let (orders, cancels, ticks) = consumer.join().unwrap();
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "shutdown should be honoured promptly"
+    );
+    // Every source made progress — none starved.
+    assert!(orders > 0 && cancels > 0 && ticks > 0, "all sources served");
+}
+
+#[test]
+fn test_rendezvous_send_blocks_until_recv() {
+    use crossbeam::channel::bounded;
+
+    // A zero-capacity channel is a rendezvous: a send completes only once a
+    // matching recv is ready (Go-style handshake).
+    let (tx, rx) = bounded::<u64>(0);
+    let completed = Arc::new(AtomicBool::new(false));
+
+    let c = completed.clone();
+    let sender = thread::spawn(move || {
+        tx.send(7).unwrap();
+        c.store(true, Ordering::SeqCst);
+    });
+
+    // With no receiver yet, the send cannot have completed.
+    thread::sleep(Duration::from_millis(20));
+    assert!(
+        !completed.load(Ordering::SeqCst),
+        "send on an unbuffered channel must block until a recv is ready"
+    );
+
+    // The matching recv releases the rendezvous.
+    assert_eq!(rx.recv().unwrap(), 7);
+    sender.join().unwrap();
+    assert!(completed.load(Ordering::SeqCst));
+}
+
 // =============================================================================
 // B7: Atomic Ordering Tests
 // =============================================================================
@@ -519,6 +828,102 @@ fn test_b10_thread_pool_bounded() {
     assert!(max_seen.load(Ordering::SeqCst) > 0);
 }
 
+// =============================================================================
+// B10b: Phase-Coordination (Barrier / WaitGroup) Tests
+// =============================================================================
+
+#[test]
+fn test_phase_transitions_use_barrier_in_source() {
+    // Phase advancement must synchronise on a barrier/wait-group, not a sleep
+    // or a hand-rolled counter that races across phases.
+    let src = read_source("services/matching/src/phases.rs");
+    let run_fn = src.split("pub fn run_pipeline").nth(1).unwrap_or("");
+    // Comment lines are skipped so a note naming the fix cannot satisfy the check.
+    let run_body: String = run_fn
+        .split("\n}")
+        .next()
+        .unwrap_or(run_fn)
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("//"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert!(
+        run_body.contains("Barrier") || run_body.contains("WaitGroup"),
+        "run_pipeline must coordinate phases with std::sync::Barrier or \
+         crossbeam_utils::sync::WaitGroup"
+    );
+    assert!(
+        !run_body.contains("thread::sleep") && !run_body.contains("sleep("),
+        "sleeping to coordinate phases races under load — use a barrier/wait-group"
+    );
+}
+
+#[test]
+fn test_barrier_keeps_workers_in_lockstep() {
+    use std::sync::Barrier;
+
+    const WORKERS: usize = 8;
+    const PHASES: u64 = 3;
+
+    // Shared phase guard: the number of workers that have entered each phase.
+    let entered = Arc::new((0..PHASES).map(|_| AtomicU64::new(0)).collect::<Vec<_>>());
+    let barrier = Arc::new(Barrier::new(WORKERS));
+
+    let handles: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let entered = entered.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                for p in 0..PHASES as usize {
+                    let n = entered[p].fetch_add(1, Ordering::SeqCst) + 1;
+                    // No worker may be in a later phase while we are in this one.
+                    for later in entered.iter().skip(p + 1) {
+                        assert_eq!(
+                            later.load(Ordering::SeqCst),
+                            0,
+                            "a worker reached a later phase before this phase completed"
+                        );
+                    }
+                    let _ = n;
+                    // All workers rendezvous before the next phase begins.
+                    barrier.wait();
+                }
+            })
+        })
+        .collect();
+
+    for h in handles {
+        h.join().unwrap();
+    }
+    for p in 0..PHASES as usize {
+        assert_eq!(entered[p].load(Ordering::SeqCst), WORKERS as u64);
+    }
+}
+
+#[test]
+fn test_waitgroup_blocks_coordinator_until_fanout_done() {
+    use crossbeam_utils::sync::WaitGroup;
+
+    // Dynamically spawned fan-out: the coordinator must not advance until every
+    // spawned task has finished.
+    let done = Arc::new(AtomicU64::new(0));
+    let wg = WaitGroup::new();
+
+    for i in 0..16u64 {
+        let wg = wg.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+            done.fetch_add(i, Ordering::SeqCst);
+            drop(wg);
+        });
+    }
+
+    wg.wait();
+    // Every task completed before wait() returned.
+    assert_eq!(done.load(Ordering::SeqCst), (0..16).sum());
+}
+
 // =============================================================================
 // B11: Lock-free ABA Problem Tests
 // =============================================================================
@@ -595,6 +1000,98 @@ fn test_b12_memory_ordering_in_source() {
          for cross-thread visibility (bug B12)");
 }
 
+// =============================================================================
+// B13: False Sharing / Cache-Line Padding Tests
+// =============================================================================
+
+#[test]
+fn test_b13_hot_counters_padded_in_source() {
+    // BUG B13: HotCounters packs independently-written atomics into one struct,
+    // so they share a cache line and ping-pong between the writing threads.
+    let src = read_source("services/matching/src/engine.rs");
+
+    // Isolate the HotCounters struct body.
+    let struct_fn = src.split("pub struct HotCounters").nth(1).unwrap_or("");
+    let struct_body = struct_fn.split('}').next().unwrap_or(struct_fn);
+
+    // Each concurrently-mutated counter must live in its own CachePadded field.
+    for field in ["sequence", "last_price", "event_count"] {
+        let line = struct_body
+            .lines()
+            .find(|l| l.contains(&format!("{}:", field)))
+            .unwrap_or("");
+        assert!(
+            line.contains("CachePadded"),
+            "HotCounters.{field} is written concurrently and must be wrapped in \
+             crossbeam_utils::CachePadded to avoid false sharing (bug B13)"
+        );
+    }
+
+    // The read-mostly instrument_id is immutable and must not be padded wastefully.
+    let id_line = struct_body
+        .lines()
+        .find(|l| l.contains("instrument_id:"))
+        .unwrap_or("");
+    assert!(
+        !id_line.contains("CachePadded"),
+        "instrument_id is read-only after construction — padding it wastes a \
+         cache line; only independently-written hot fields should be padded (bug B13)"
+    );
+}
+
+#[test]
+fn test_b13_packed_and_separated_atomics_count_correctly() {
+    use crossbeam_utils::CachePadded;
+
+    // Two atomics written by two threads: on the same cache line they contend;
+    // on separate lines they do not. Both layouts must stay correct — this
+    // guards the behavior, not the wall-clock win.
+    const ITERS: u64 = 200_000;
+
+    fn hammer(a: &AtomicU64, b: &AtomicU64) -> Duration {
+        let start = Instant::now();
+        thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    a.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    b.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        });
+        start.elapsed()
+    }
+
+    // Packed: both counters adjacent in one struct (shared line).
+    struct Packed {
+        a: AtomicU64,
+        b: AtomicU64,
+    }
+    let packed = Packed {
+        a: AtomicU64::new(0),
+        b: AtomicU64::new(0),
+    };
+    let _packed_time = hammer(&packed.a, &packed.b);
+    assert_eq!(packed.a.load(Ordering::Relaxed), ITERS);
+    assert_eq!(packed.b.load(Ordering::Relaxed), ITERS);
+
+    // Separated: each counter on its own cache line.
+    struct Separated {
+        a: CachePadded<AtomicU64>,
+        b: CachePadded<AtomicU64>,
+    }
+    let separated = Separated {
+        a: CachePadded::new(AtomicU64::new(0)),
+        b: CachePadded::new(AtomicU64::new(0)),
+    };
+    let _separated_time = hammer(&separated.a, &separated.b);
+    assert_eq!(separated.a.load(Ordering::Relaxed), ITERS);
+    assert_eq!(separated.b.load(Ordering::Relaxed), ITERS);
+}
+
 // =============================================================================
 // Additional Concurrency Tests
 // =============================================================================
@@ -645,6 +1142,78 @@ fn test_rwlock_readers() {
     // Should complete without contention issues
 }
 
+#[test]
+fn test_snapshot_store_uses_sharded_lock_in_source() {
+    // The read-heavy snapshot store must migrate to a ShardedLock so readers
+    // scale across cores; a single RwLock or a Mutex both serialise readers.
+    let src = read_source("services/matching/src/snapshot.rs");
+    let struct_fn = src.split("pub struct SnapshotStore").nth(1).unwrap_or("");
+    // Only field declarations count: comment lines are skipped so a note
+    // naming the fix cannot satisfy the check.
+    let struct_body: String = struct_fn
+        .split('}')
+        .next()
+        .unwrap_or(struct_fn)
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("//"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert!(
+        struct_body.contains("ShardedLock"),
+        "SnapshotStore is read-heavy and should guard its snapshot with \
+         crossbeam_utils::sync::ShardedLock for reader scaling"
+    );
+    assert!(
+        !struct_body.contains("Mutex"),
+        "a Mutex serialises readers — the snapshot path needs a reader-scaling \
+         lock, not mutual exclusion"
+    );
+}
+
+#[test]
+fn test_snapshot_store_many_readers_one_writer() {
+    use crossbeam_utils::sync::ShardedLock;
+
+    // A reader-scaling store: readers take per-shard locks, the writer all shards.
+    let store: Arc<ShardedLock<std::collections::HashMap<&'static str, i64>>> =
+        Arc::new(ShardedLock::new(std::collections::HashMap::new()));
+    store.write().unwrap().insert("AAPL", 0);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let readers: Vec<_> = (0..8)
+        .map(|_| {
+            let s = store.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut last = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    // No torn reads: the value is monotonic non-decreasing.
+                    let v = *s.read().unwrap().get("AAPL").unwrap();
+                    assert!(v >= last, "reader observed a stale/torn value");
+                    last = v;
+                }
+                last
+            })
+        })
+        .collect();
+
+    let w = store.clone();
+    let writer = thread::spawn(move || {
+        for i in 1..=1000 {
+            w.write().unwrap().insert("AAPL", i);
+        }
+    });
+    writer.join().unwrap();
+    stop.store(true, Ordering::Relaxed);
+
+    for r in readers {
+        r.join().unwrap();
+    }
+    // The final write is eventually visible to all.
+    assert_eq!(*store.read().unwrap().get("AAPL").unwrap(), 1000);
+}
+
 #[test]
 fn test_rwlock_writer_priority() {
     let data = Arc::new(RwLock::new(0u64));
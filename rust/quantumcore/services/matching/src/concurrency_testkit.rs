@@ -0,0 +1,104 @@
+//! Shared concurrency primitives for model-checked tests.
+//!
+//! Under `--cfg loom` this module re-exports [`loom`]'s instrumented `sync`
+//! and `thread` types so `loom::model(|| …)` can exhaustively explore every
+//! thread schedule and memory reordering permitted by the chosen `Ordering`.
+//! Without the flag it re-exports the equivalent `std` types, so production
+//! code and ordinary `cargo test` runs are unaffected.
+//!
+//! Enable the model checker with:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test loom_tests
+//! ```
+
+#[cfg(loom)]
+pub use loom::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+#[cfg(loom)]
+pub use loom::sync::{Arc, Mutex, RwLock};
+#[cfg(loom)]
+pub use loom::thread;
+
+#[cfg(not(loom))]
+pub use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+#[cfg(not(loom))]
+pub use std::sync::{Arc, Mutex, RwLock};
+#[cfg(not(loom))]
+pub use std::thread;
+
+/// Memory-ordering scenarios expressed against the testkit primitives so they
+/// run as ordinary threads normally and under the exhaustive loom scheduler
+/// with `--cfg loom`. Each `loom::model` closure is explored over all
+/// interleavings; a `Relaxed` store where a `Release`/`Acquire` pair is
+/// required — e.g. in `update_last_price` or a producer publishing a sequence
+/// number — fails the model.
+#[cfg(loom)]
+#[cfg(test)]
+mod loom_tests {
+    use super::*;
+
+    /// B3: concurrent read-modify-write must be atomic under every schedule.
+    #[test]
+    fn b3_read_modify_write_atomic() {
+        loom::model(|| {
+            let counter = Arc::new(AtomicU64::new(0));
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let c = counter.clone();
+                    thread::spawn(move || {
+                        c.fetch_add(1, Ordering::SeqCst);
+                    })
+                })
+                .collect();
+            for h in handles {
+                h.join().unwrap();
+            }
+            assert_eq!(counter.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    /// B7: a `Release` store published behind an `Acquire`-loaded flag must be
+    /// visible whenever the flag is observed, across all reorderings.
+    #[test]
+    fn b7_atomic_ordering_correct() {
+        loom::model(|| {
+            let value = Arc::new(AtomicU64::new(0));
+            let flag = Arc::new(AtomicBool::new(false));
+
+            let (v, f) = (value.clone(), flag.clone());
+            let producer = thread::spawn(move || {
+                v.store(42, Ordering::Relaxed);
+                f.store(true, Ordering::Release);
+            });
+
+            if flag.load(Ordering::Acquire) {
+                // The Relaxed store above is ordered before the flag's Release,
+                // so an Acquire observer of the flag must see the value.
+                assert_eq!(value.load(Ordering::Relaxed), 42);
+            }
+            producer.join().unwrap();
+        });
+    }
+
+    /// B12: price and sequence published together must never be observed torn.
+    #[test]
+    fn b12_memory_ordering_prices() {
+        loom::model(|| {
+            let price = Arc::new(AtomicU64::new(0));
+            let sequence = Arc::new(AtomicU64::new(0));
+
+            let (p, s) = (price.clone(), sequence.clone());
+            let producer = thread::spawn(move || {
+                p.store(100, Ordering::Release);
+                s.store(1, Ordering::Release);
+            });
+
+            // Load sequence first, then price: if we saw the sequence we must
+            // also see the matching price.
+            let seq = sequence.load(Ordering::Acquire);
+            let prc = price.load(Ordering::Acquire);
+            assert!(seq == 0 || prc == seq * 100);
+            producer.join().unwrap();
+        });
+    }
+}
@@ -51,6 +51,39 @@ pub struct MatchEvent {
     pub quantity: u64,
 }
 
+pub struct HotCounters {
+
+    // B13: sequence, last_price and event_count are each written by a different
+    // thread (the matcher, the price-feed, the event fan-out) but packed into
+    // one struct, so they share a cache line and ping-pong it on every update.
+    // Each independently-written atomic should sit on its own line via
+    // crossbeam_utils::CachePadded; instrument_id is immutable after
+    // construction and must stay unpadded.
+    pub sequence: std::sync::atomic::AtomicU64,
+    pub last_price: std::sync::atomic::AtomicU64,
+    pub event_count: std::sync::atomic::AtomicU64,
+    pub instrument_id: u64,
+}
+
+impl HotCounters {
+    pub fn new(instrument_id: u64) -> Self {
+        use std::sync::atomic::AtomicU64;
+        Self {
+            sequence: AtomicU64::new(0),
+            last_price: AtomicU64::new(0),
+            event_count: AtomicU64::new(0),
+            instrument_id,
+        }
+    }
+
+    pub fn record_trade(&self, price_bits: u64) -> u64 {
+        use std::sync::atomic::Ordering;
+        self.last_price.store(price_bits, Ordering::Release);
+        self.event_count.fetch_add(1, Ordering::Relaxed);
+        self.sequence.fetch_add(1, Ordering::Release) + 1
+    }
+}
+
 impl MatchingEngine {
     pub fn new() -> (Self, mpsc::UnboundedReceiver<MatchEvent>) {
         let (tx, rx) = mpsc::unbounded_channel();
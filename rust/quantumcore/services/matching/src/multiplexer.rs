@@ -0,0 +1,55 @@
+//! Market-event multiplexer for the matching service.
+//!
+//! The matcher consumes from several independent sources — new orders, cancel
+//! requests, market-data ticks and a shutdown signal — and must service them
+//! fairly while reacting to shutdown promptly. The consumer loop below
+//! busy-polls each source with `try_recv` in a tight loop, which pins a core
+//! even when every channel is empty and lets a hot source starve the others.
+//! The fix is to block on all sources at once with `crossbeam::channel::select!`
+//! so the OS wakes the loop only when work (or shutdown) is actually ready.
+
+use crossbeam::channel::Receiver;
+
+/// A demultiplexed event handed to the matcher.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketEvent {
+    Order(u64),
+    Cancel(u64),
+    Tick(u64),
+}
+
+/// The four input channels the matcher listens on.
+pub struct Sources {
+    pub orders: Receiver<u64>,
+    pub cancels: Receiver<u64>,
+    pub ticks: Receiver<u64>,
+    pub shutdown: Receiver<()>,
+}
+
+/// Drain every source until shutdown is signalled, appending each event to
+/// `sink` in the order it is handled. Returns the number of events handled.
+pub fn run(sources: Sources, sink: &mut Vec<MarketEvent>) -> usize {
+    let mut handled = 0;
+
+    // BUG (channel spin): busy-polling every source with try_recv burns a core
+    // while the channels are empty and drains `orders` greedily before ever
+    // looking at the other sources, starving them. This should block on all
+    // four with crossbeam::channel::select! and handle whichever is ready.
+    loop {
+        if sources.shutdown.try_recv().is_ok() {
+            return handled;
+        }
+        while let Ok(id) = sources.orders.try_recv() {
+            sink.push(MarketEvent::Order(id));
+            handled += 1;
+        }
+        while let Ok(id) = sources.cancels.try_recv() {
+            sink.push(MarketEvent::Cancel(id));
+            handled += 1;
+        }
+        while let Ok(id) = sources.ticks.try_recv() {
+            sink.push(MarketEvent::Tick(id));
+            handled += 1;
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Phased settlement pipeline for the matching service.
+//!
+//! Each batch moves through three phases — load → match → settle — and every
+//! worker must finish phase N before any worker starts phase N+1 (a match must
+//! not run against half-loaded state, a settle must not run against a
+//! half-matched book). In the committed version each worker sleeps for a fixed
+//! interval between phases and hopes every other worker has caught up, which
+//! races under load.
+//! The fix is a `std::sync::Barrier` for the fixed worker pool and a
+//! `crossbeam_utils::sync::WaitGroup` for dynamically spawned fan-out that the
+//! coordinator waits on before advancing the phase.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The phases a batch passes through, in order.
+pub const PHASES: [&str; 3] = ["load", "match", "settle"];
+
+/// Run `workers` through all [`PHASES`], returning the shared phase guard so
+/// callers can inspect the highest phase reached.
+pub fn run_pipeline(workers: usize) -> Arc<AtomicU64> {
+    let phase = Arc::new(AtomicU64::new(0));
+
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let phase = phase.clone();
+            thread::spawn(move || {
+                for p in 0..PHASES.len() as u64 {
+                    // Do this phase's work.
+                    phase.fetch_max(p, Ordering::SeqCst);
+
+                    // BUG (phase race): sleeping instead of synchronising on a
+                    // barrier means a fast worker can advance to phase N+1 while
+                    // a slow worker is still in phase N. Coordinate with
+                    // std::sync::Barrier (fixed pool) or a
+                    // crossbeam_utils::sync::WaitGroup (dynamic fan-out) here.
+                    thread::sleep(Duration::from_millis(1));
+                }
+            })
+        })
+        .collect();
+
+    for h in handles {
+        h.join().unwrap();
+    }
+    phase
+}
@@ -0,0 +1,269 @@
+//! Lock-free bounded MPMC queue for the market-data fan-out path.
+//!
+//! This is the Vyukov bounded-queue algorithm (the same one behind crossbeam's
+//! `ArrayQueue`): each slot carries a `stamp` that encodes whose turn it is, so
+//! producers and consumers coordinate with a single CAS per operation and never
+//! block. Multiple producers and multiple consumers are safe.
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam_utils::CachePadded;
+
+/// One ring slot: a `stamp` sequencing access plus the (possibly uninitialized)
+/// value. The stamp alternates between "ready to be written by this lap" and
+/// "holds a value written this lap" so head/tail cursors can tell full from
+/// empty without a shared count.
+struct Slot<T> {
+    stamp: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A bounded, lock-free multi-producer/multi-consumer queue.
+pub struct RingBuffer<T> {
+    buffer: Box<[CachePadded<Slot<T>>]>,
+    /// Mask for extracting the slot index from a cursor (`one_lap - 1`).
+    index_mask: usize,
+    /// `capacity.next_power_of_two()`: added to a cursor to advance one lap, so
+    /// a cursor packs `lap_bits | index`.
+    one_lap: usize,
+    head: CachePadded<AtomicUsize>,
+    tail: CachePadded<AtomicUsize>,
+}
+
+unsafe impl<T: Send> Send for RingBuffer<T> {}
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T> RingBuffer<T> {
+    /// Create a queue holding up to `capacity` items. Panics if `capacity` is
+    /// zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be non-zero");
+        let one_lap = capacity.next_power_of_two();
+
+        let buffer: Box<[CachePadded<Slot<T>>]> = (0..capacity)
+            .map(|i| {
+                // Slot i starts its life expecting to be written at cursor i.
+                CachePadded::new(Slot {
+                    stamp: AtomicUsize::new(i),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+            })
+            .collect();
+
+        Self {
+            buffer,
+            index_mask: one_lap - 1,
+            one_lap,
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Number of items the queue can hold.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Push `value`, returning it back in `Err` if the queue is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        loop {
+            let index = tail & self.index_mask;
+            let slot = &self.buffer[index];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+
+            if stamp == tail {
+                // The slot is ready for this lap — try to claim the tail.
+                let new_tail = next_cursor(tail, index, self.capacity(), self.one_lap);
+                match self.tail.compare_exchange_weak(
+                    tail,
+                    new_tail,
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: we own this slot until we bump its stamp.
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.stamp.store(tail + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(t) => tail = t,
+                }
+            } else if stamp.wrapping_add(self.one_lap) == tail + 1 {
+                // The slot is a full lap behind: the queue is full.
+                return Err(value);
+            } else {
+                tail = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pop the oldest item, or `None` if the queue is empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let index = head & self.index_mask;
+            let slot = &self.buffer[index];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+
+            if stamp == head + 1 {
+                // A value written this lap is waiting — try to claim the head.
+                let new_head = next_cursor(head, index, self.capacity(), self.one_lap);
+                match self.head.compare_exchange_weak(
+                    head,
+                    new_head,
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: our successful CAS gives us sole ownership.
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        // Ready the slot for the next lap.
+                        slot.stamp
+                            .store(head.wrapping_add(self.one_lap), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(h) => head = h,
+                }
+            } else if stamp == head {
+                // The producer has not finished this slot: the queue is empty.
+                return None;
+            } else {
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Current number of buffered items.
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+            // Re-read to guard against a cursor moving mid-computation.
+            if self.tail.load(Ordering::SeqCst) == tail {
+                let hix = head & self.index_mask;
+                let tix = tail & self.index_mask;
+                return if hix < tix {
+                    tix - hix
+                } else if hix > tix {
+                    self.capacity() - hix + tix
+                } else if tail == head {
+                    0
+                } else {
+                    self.capacity()
+                };
+            }
+        }
+    }
+
+    /// Whether the queue currently holds no items.
+    pub fn is_empty(&self) -> bool {
+        let head = self.head.load(Ordering::SeqCst);
+        let tail = self.tail.load(Ordering::SeqCst);
+        head == tail
+    }
+
+    /// Whether the queue is currently at capacity.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+}
+
+impl<T> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        // Drain any remaining initialized values so their destructors run.
+        while self.pop().is_some() {}
+    }
+}
+
+/// Advance a cursor to the next slot, wrapping the index to the start of the
+/// next lap once the final slot (`capacity - 1`) is reached. `capacity` may be
+/// smaller than `one_lap` when it is not a power of two.
+fn next_cursor(cursor: usize, index: usize, capacity: usize, one_lap: usize) -> usize {
+    if index + 1 < capacity {
+        cursor + 1
+    } else {
+        cursor.wrapping_add(one_lap).wrapping_sub(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn push_pop_fifo() {
+        let q = RingBuffer::new(4);
+        assert!(q.is_empty());
+        for i in 0..4 {
+            q.push(i).unwrap();
+        }
+        assert!(q.is_full());
+        assert_eq!(q.push(99), Err(99));
+        for i in 0..4 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert!(q.pop().is_none());
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn wraps_around_laps() {
+        let q = RingBuffer::new(2);
+        for i in 0..10 {
+            q.push(i).unwrap();
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn mpmc_conserves_every_item() {
+        const PRODUCERS: u64 = 4;
+        const CONSUMERS: u64 = 4;
+        const PER_PRODUCER: u64 = 10_000;
+
+        let q: Arc<RingBuffer<u64>> = Arc::new(RingBuffer::new(64));
+        let consumed = Arc::new(AtomicUsize::new(0));
+        let sum = Arc::new(AtomicU64::new(0));
+
+        let mut handles = Vec::new();
+        for _ in 0..CONSUMERS {
+            let q = q.clone();
+            let consumed = consumed.clone();
+            let sum = sum.clone();
+            handles.push(thread::spawn(move || {
+                let total = (PRODUCERS * PER_PRODUCER) as usize;
+                while consumed.load(Ordering::Relaxed) < total {
+                    if let Some(v) = q.pop() {
+                        sum.fetch_add(v, Ordering::Relaxed);
+                        consumed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }));
+        }
+        for p in 0..PRODUCERS {
+            let q = q.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..PER_PRODUCER {
+                    let value = p * PER_PRODUCER + i;
+                    while q.push(value).is_err() {}
+                }
+            }));
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        // Every produced value was consumed exactly once (Gauss sum check).
+        let n = PRODUCERS * PER_PRODUCER;
+        let expected = (0..n).sum::<u64>();
+        assert_eq!(sum.load(Ordering::Relaxed), expected);
+        assert!(q.is_empty());
+    }
+}
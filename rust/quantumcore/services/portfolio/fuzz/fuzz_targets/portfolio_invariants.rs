@@ -0,0 +1,123 @@
+#![no_main]
+
+//! libFuzzer target driving [`PortfolioManager`] with randomized sequences of
+//! `update_position` calls and asserting the core valuation invariants after
+//! every step. The raw fuzzer input is decoded into a deterministic operation
+//! log so a crashing input can be shrunk and replayed from the CI corpus.
+//!
+//! Invariants checked after each op:
+//!   * the signed sum of per-position market values plus cash equals the
+//!     cached `Portfolio` total,
+//!   * a net-zero-quantity position carries zero market value,
+//!   * valuation never panics or overflows (errors are structured), and
+//!   * replaying the whole log on a fresh manager yields an identical book.
+
+use libfuzzer_sys::fuzz_target;
+use portfolio::manager::PortfolioManager;
+use rust_decimal::Decimal;
+
+/// One decoded fill: which account/symbol, the signed quantity delta, and the
+/// fill price. Symbols are drawn from a small table so collisions are frequent
+/// (that is where the interesting netting happens); account ids include
+/// unicode and punctuation to exercise the string-keyed maps.
+#[derive(Clone)]
+struct Op {
+    account: String,
+    symbol: String,
+    delta: i64,
+    price: Decimal,
+}
+
+const ACCOUNTS: &[&str] = &["acc1", "acc_2", "用户三", "a@b.c", "Ωmega", "🦀"];
+const SYMBOLS: &[&str] = &["AAPL", "BRK.A", "PENNY", "MSFT"];
+
+/// Decode the fuzzer bytes into an operation log. Each op consumes a fixed
+/// stride of bytes; a short trailing remainder just ends the log.
+fn decode(data: &[u8]) -> Vec<Op> {
+    let mut ops = Vec::new();
+    for chunk in data.chunks(11) {
+        if chunk.len() < 11 {
+            break;
+        }
+        let account = ACCOUNTS[chunk[0] as usize % ACCOUNTS.len()].to_string();
+        let symbol = SYMBOLS[chunk[1] as usize % SYMBOLS.len()].to_string();
+
+        // Signed quantity spanning small and large magnitudes.
+        let raw_qty = i32::from_le_bytes([chunk[2], chunk[3], chunk[4], chunk[5]]);
+        let delta = raw_qty as i64;
+
+        // A wide spread of decimals: mantissa up to ~4.2e9 scaled by 0..=9.
+        let mantissa = u32::from_le_bytes([chunk[6], chunk[7], chunk[8], chunk[9]]) as i64;
+        let scale = (chunk[10] % 10) as u32;
+        let price = Decimal::new(mantissa, scale);
+
+        ops.push(Op { account, symbol, delta, price });
+    }
+    ops
+}
+
+/// Apply the whole log to a fresh manager, asserting the running invariants.
+/// Returns the final portfolios keyed by account for the determinism check.
+fn run(ops: &[Op]) -> Vec<(String, String)> {
+    let manager = PortfolioManager::new(60);
+    let mut touched = std::collections::BTreeSet::new();
+
+    for op in ops {
+        // A zero delta is a no-op fill; skip it so `average_cost` stays defined.
+        if op.delta == 0 {
+            continue;
+        }
+        // Errors (e.g. overflow on an extreme decimal) are acceptable — the
+        // contract is only that we never panic.
+        if manager
+            .update_position(&op.account, &op.symbol, op.delta, op.price)
+            .is_err()
+        {
+            continue;
+        }
+        touched.insert(op.account.clone());
+
+        let portfolio = match manager.get_portfolio(&op.account) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+
+        let mut signed_sum = portfolio.cash_balance;
+        for pos in portfolio.positions.values() {
+            signed_sum += pos.market_value * Decimal::from(pos.quantity.signum());
+            if pos.quantity == 0 {
+                assert!(pos.market_value.is_zero(), "net-zero position has value");
+            }
+        }
+        assert_eq!(signed_sum, portfolio.total_value, "market values must sum to total");
+    }
+
+    // Snapshot each touched account into a canonical (sorted, timestamp-free)
+    // form so the determinism comparison is insensitive to map iteration order
+    // and the volatile `last_updated` clock.
+    touched
+        .into_iter()
+        .filter_map(|acc| {
+            let p = manager.get_portfolio(&acc).ok()?;
+            let mut positions: Vec<(String, i64, Decimal)> = p
+                .positions
+                .values()
+                .map(|pos| (pos.symbol.clone(), pos.quantity, pos.market_value))
+                .collect();
+            positions.sort();
+            let canonical = format!(
+                "cash={};total={};realized={};fees={};{:?}",
+                p.cash_balance, p.total_value, p.realized_pnl, p.fees_paid, positions
+            );
+            Some((acc, canonical))
+        })
+        .collect()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let ops = decode(data);
+    let first = run(&ops);
+    let second = run(&ops);
+    // Replaying the same log on a fresh manager must produce an identical book.
+    assert_eq!(first, second, "replay must be deterministic");
+});
@@ -1,22 +1,224 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use rust_decimal::Decimal;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 
 
 
+#[derive(Debug, thiserror::Error)]
+pub enum PortfolioError {
+    #[error("Portfolio not found")]
+    NotFound,
+    #[error("Decimal arithmetic overflow")]
+    Overflow,
+    #[error("Division by zero")]
+    DivByZero,
+}
+
+// Checked-math helpers: every `Decimal` operation in the valuation path routes
+// through these so a `None` result (overflow) or a zero denominator becomes a
+// structured [`PortfolioError`] instead of a panic or a wrong `total_value`.
+fn checked_mul(a: Decimal, b: Decimal) -> Result<Decimal, PortfolioError> {
+    a.checked_mul(b).ok_or(PortfolioError::Overflow)
+}
+
+fn checked_add(a: Decimal, b: Decimal) -> Result<Decimal, PortfolioError> {
+    a.checked_add(b).ok_or(PortfolioError::Overflow)
+}
+
+fn checked_sub(a: Decimal, b: Decimal) -> Result<Decimal, PortfolioError> {
+    a.checked_sub(b).ok_or(PortfolioError::Overflow)
+}
+
+fn checked_div(a: Decimal, b: Decimal) -> Result<Decimal, PortfolioError> {
+    if b.is_zero() {
+        return Err(PortfolioError::DivByZero);
+    }
+    a.checked_div(b).ok_or(PortfolioError::Overflow)
+}
+
+/// Apply a fill to a position's lots, returning the realized PnL booked by any
+/// closed quantity. Adding to the position opens a lot (or merges into the
+/// single average-cost lot); a reducing fill consumes lots in the configured
+/// order, and a fill that crosses through zero closes all remaining lots and
+/// opens a fresh lot for the residual in the new direction.
+fn apply_fill(position: &mut PortfolioPosition, delta: i64, price: Decimal, method: LotMethod) -> Decimal {
+    let old_qty = position.quantity;
+    let opening = old_qty == 0 || (old_qty > 0) == (delta > 0);
+    if opening {
+        add_lot(&mut position.lots, delta, price, method);
+        return Decimal::ZERO;
+    }
+
+    let mut realized = Decimal::ZERO;
+    let mut remaining = delta.abs();
+    while remaining > 0 {
+        let Some(lot) = position.lots.first_mut() else { break };
+        let lot_qty = lot.quantity.abs();
+        let closed = remaining.min(lot_qty);
+        let closed_dec = Decimal::from(closed);
+        if lot.quantity > 0 {
+            // Closing a long: gain when selling above the lot cost.
+            realized += (price - lot.cost) * closed_dec;
+            lot.quantity -= closed;
+        } else {
+            // Closing a short: gain when covering below the lot cost.
+            realized += (lot.cost - price) * closed_dec;
+            lot.quantity += closed;
+        }
+        remaining -= closed;
+        if lot.quantity == 0 {
+            position.lots.remove(0);
+        }
+    }
+
+    // Crossed through zero: the residual opens a new lot in the fill direction.
+    if remaining > 0 {
+        let residual = if delta > 0 { remaining } else { -remaining };
+        add_lot(&mut position.lots, residual, price, method);
+    }
+
+    realized
+}
+
+fn add_lot(lots: &mut Vec<CostLot>, quantity: i64, price: Decimal, method: LotMethod) {
+    match method {
+        LotMethod::Fifo => lots.push(CostLot { quantity, cost: price }),
+        LotMethod::AverageCost => {
+            if let Some(lot) = lots.first_mut() {
+                let combined = lot.quantity + quantity;
+                let old_notional = lot.cost * Decimal::from(lot.quantity.abs());
+                let add_notional = price * Decimal::from(quantity.abs());
+                if combined != 0 {
+                    lot.cost = (old_notional + add_notional) / Decimal::from(combined.abs());
+                }
+                lot.quantity = combined;
+            } else {
+                lots.push(CostLot { quantity, cost: price });
+            }
+        }
+    }
+}
+
+fn weighted_average_cost(lots: &[CostLot]) -> Decimal {
+    let total_qty: i64 = lots.iter().map(|l| l.quantity.abs()).sum();
+    if total_qty == 0 {
+        return Decimal::ZERO;
+    }
+    let notional: Decimal = lots.iter().map(|l| l.cost * Decimal::from(l.quantity.abs())).sum();
+    notional / Decimal::from(total_qty)
+}
+
+/// Reduce a fractional share count to a whole number of shares under the
+/// requested [`RoundingMode`], preserving sign.
+fn round_shares(raw: Decimal, mode: RoundingMode) -> i64 {
+    use rust_decimal::RoundingStrategy;
+    let rounded = match mode {
+        RoundingMode::Nearest => raw.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero),
+        RoundingMode::Down => raw.trunc(),
+        RoundingMode::Up => {
+            let truncated = raw.trunc();
+            if raw == truncated {
+                truncated
+            } else if raw.is_sign_negative() {
+                truncated - Decimal::ONE
+            } else {
+                truncated + Decimal::ONE
+            }
+        }
+    };
+    rounded.to_i64().unwrap_or(0)
+}
+
+/// Theoretical price and greeks of a BSM option as of `as_of`. Computed in
+/// `f64` for the transcendental functions, then carried back into `Decimal`.
+/// Once expired (or at zero vol) it collapses to intrinsic value.
+fn price_option(spec: &OptionSpec, as_of: NaiveDate) -> (Decimal, Greeks) {
+    use std::f64::consts::PI;
+
+    let s = spec.spot.to_f64().unwrap_or(0.0);
+    let k = spec.strike.to_f64().unwrap_or(0.0);
+    let sigma = spec.volatility.to_f64().unwrap_or(0.0);
+    let r = spec.rate.to_f64().unwrap_or(0.0);
+    let t = (spec.expiry - as_of).num_days() as f64 / 365.0;
+
+    let to_dec = |x: f64| Decimal::from_f64(x).unwrap_or(Decimal::ZERO);
+
+    if t <= 0.0 || sigma <= 0.0 {
+        let intrinsic = if spec.is_call { (s - k).max(0.0) } else { (k - s).max(0.0) };
+        let delta = if spec.is_call {
+            if s > k { 1.0 } else { 0.0 }
+        } else if s < k { -1.0 } else { 0.0 };
+        return (to_dec(intrinsic), Greeks { delta: to_dec(delta), ..Default::default() });
+    }
+
+    // Standard normal CDF via erf, and PDF.
+    let norm_cdf = |x: f64| 0.5 * (1.0 + erf(x / 2.0_f64.sqrt()));
+    let norm_pdf = |x: f64| (-0.5 * x * x).exp() / (2.0 * PI).sqrt();
+
+    let d1 = ((s / k).ln() + (r + 0.5 * sigma * sigma) * t) / (sigma * t.sqrt());
+    let d2 = d1 - sigma * t.sqrt();
+    let disc = (-r * t).exp();
+
+    let (price, delta) = if spec.is_call {
+        (s * norm_cdf(d1) - k * disc * norm_cdf(d2), norm_cdf(d1))
+    } else {
+        (k * disc * norm_cdf(-d2) - s * norm_cdf(-d1), norm_cdf(d1) - 1.0)
+    };
+
+    let gamma = norm_pdf(d1) / (s * sigma * t.sqrt());
+    let vega = s * norm_pdf(d1) * t.sqrt() / 100.0; // per 1% vol move
+    let theta = {
+        let term1 = -(s * norm_pdf(d1) * sigma) / (2.0 * t.sqrt());
+        let term2 = if spec.is_call {
+            -r * k * disc * norm_cdf(d2)
+        } else {
+            r * k * disc * norm_cdf(-d2)
+        };
+        (term1 + term2) / 365.0 // per calendar day
+    };
+
+    (
+        to_dec(price),
+        Greeks {
+            delta: to_dec(delta),
+            gamma: to_dec(gamma),
+            vega: to_dec(vega),
+            theta: to_dec(theta),
+        },
+    )
+}
+
+/// Abramowitz & Stegun 7.1.26 rational approximation of the error function.
+fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let y = 1.0
+        - (((((1.061405429 * t - 1.453152027) * t) + 1.421413741) * t - 0.284496736) * t
+            + 0.254829592)
+            * t
+            * (-x * x).exp();
+    sign * y
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Portfolio {
     pub account_id: String,
     pub positions: HashMap<String, PortfolioPosition>,
     pub cash_balance: Decimal,
     pub total_value: Decimal,
+    /// Cumulative realized PnL booked as positions are reduced or covered.
+    pub realized_pnl: Decimal,
+    /// Cumulative commissions charged against this account.
+    pub fees_paid: Decimal,
     pub last_updated: DateTime<Utc>,
 }
 
@@ -29,16 +231,264 @@ pub struct PortfolioPosition {
     pub market_value: Decimal,
     pub unrealized_pnl: Decimal,
     pub unrealized_pnl_percent: Decimal,
+    /// Slow-moving EMA of the oracle price, used by the less-manipulable
+    /// valuation modes. Seeded to the first observed price.
+    pub stable_price: Decimal,
+    /// Open cost lots backing this position, in acquisition order.
+    pub lots: Vec<CostLot>,
+    /// Denomination currency of this position's prices and market value.
+    pub currency: String,
+    /// Optional pricing model; when set, [`PortfolioManager::reprice`] drives
+    /// `current_price`/`market_value` instead of a flat per-share price.
+    pub instrument: Option<Instrument>,
+}
+
+impl PortfolioPosition {
+    /// Mark-to-market gain/loss against the average cost basis:
+    /// `(current_price - avg_cost) * quantity`. The signed quantity makes the
+    /// result correct for shorts as well as longs.
+    pub fn unrealized_pnl(&self) -> Decimal {
+        (self.current_price - self.average_cost) * Decimal::from(self.quantity)
+    }
+
+    /// Cost basis of the open position: `avg_cost * |quantity|`.
+    pub fn cost_basis(&self) -> Decimal {
+        self.average_cost * Decimal::from(self.quantity.abs())
+    }
+}
+
+impl Portfolio {
+    /// Summed cost basis across all open positions.
+    pub fn total_cost(&self) -> Decimal {
+        self.positions.values().map(|p| p.cost_basis()).sum()
+    }
+
+    /// Current valuation, including cash (the cached aggregate).
+    pub fn total_value(&self) -> Decimal {
+        self.total_value
+    }
+
+    /// Realized plus unrealized profit across the book.
+    pub fn total_profit(&self) -> Decimal {
+        let unrealized: Decimal = self.positions.values().map(|p| p.unrealized_pnl()).sum();
+        self.realized_pnl + unrealized
+    }
+}
+
+/// Market-value totals broken down by denomination currency plus the sum
+/// converted into a single reporting currency.
+#[derive(Debug, Clone)]
+pub struct CurrencyReport {
+    pub reporting_currency: String,
+    pub reporting_total: Decimal,
+    pub per_currency: HashMap<String, Decimal>,
+}
+
+/// How a fractional share count is reduced to whole shares when sizing a
+/// rebalance trade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round to the nearest whole share (ties away from zero).
+    Nearest,
+    /// Round toward zero, never trading more than the drift implies.
+    Down,
+    /// Round away from zero, always closing at least the drifted amount.
+    Up,
+}
+
+/// Knobs for [`PortfolioManager::rebalance`]: how to round share counts and an
+/// optional floor below which a trade's notional is treated as noise and
+/// suppressed. Defaults round to the nearest share with no threshold.
+#[derive(Debug, Clone, Copy)]
+pub struct RebalanceConfig {
+    pub rounding: RoundingMode,
+    pub min_trade_notional: Option<Decimal>,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self { rounding: RoundingMode::Nearest, min_trade_notional: None }
+    }
+}
+
+/// A single order produced by a rebalance: the symbol, the signed share
+/// quantity to trade (negative sells, positive buys), and the estimated
+/// notional at the current mark.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebalanceTrade {
+    pub symbol: String,
+    pub quantity: i64,
+    pub estimated_notional: Decimal,
+}
+
+/// Commission schedule charged on each fill: a basis-point rate with an
+/// optional cumulative per-account cap. Defaults to zero so valuation tests
+/// are unaffected.
+#[derive(Debug, Clone, Copy)]
+pub struct FeeSchedule {
+    pub rate_bps: Decimal,
+    pub per_account_cap: Option<Decimal>,
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self { rate_bps: Decimal::ZERO, per_account_cap: None }
+    }
+}
+
+impl FeeSchedule {
+    /// Largest rate we accept, in basis points (10%). Anything above is almost
+    /// certainly a misconfiguration.
+    const MAX_RATE_BPS: Decimal = dec!(1000);
+
+    /// Build a schedule, rejecting a negative or absurdly large rate.
+    pub fn new(rate_bps: Decimal, per_account_cap: Option<Decimal>) -> Result<Self> {
+        if rate_bps < Decimal::ZERO || rate_bps > Self::MAX_RATE_BPS {
+            anyhow::bail!("fee rate {rate_bps} bps out of range [0, {}]", Self::MAX_RATE_BPS);
+        }
+        Ok(Self { rate_bps, per_account_cap })
+    }
+
+    /// Fee for a fill of `qty` shares at `price`, before any per-account cap.
+    fn fee_for(&self, qty: i64, price: Decimal) -> Decimal {
+        Decimal::from(qty.abs()) * price * self.rate_bps / dec!(10000)
+    }
+}
+
+/// A Black–Scholes–Merton European option. Market inputs (spot, vol, rate)
+/// are repriced by [`PortfolioManager::reprice`]; the expiry fixes time decay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionSpec {
+    pub spot: Decimal,
+    pub strike: Decimal,
+    pub volatility: Decimal,
+    pub rate: Decimal,
+    pub expiry: NaiveDate,
+    pub is_call: bool,
+}
+
+/// A pricing model backing a position instead of a flat per-share price.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Instrument {
+    Option(OptionSpec),
+}
+
+/// Option risk sensitivities (delta, gamma, vega, theta), summed across a book by
+/// [`PortfolioManager::portfolio_greeks`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Greeks {
+    pub delta: Decimal,
+    pub gamma: Decimal,
+    pub vega: Decimal,
+    pub theta: Decimal,
+}
+
+/// A single cost lot. `quantity` is signed: positive for long lots, negative
+/// for short lots, so a position's lots always share the position's direction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostLot {
+    pub quantity: i64,
+    pub cost: Decimal,
+}
+
+/// How lots are consumed when a position is reduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LotMethod {
+    /// First-in, first-out: oldest lots closed first.
+    Fifo,
+    /// Collapse lots into a single average-cost lot.
+    AverageCost,
+}
+
+/// Which weighting schedule a health computation applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthType {
+    /// Initial-margin weights — the stricter schedule applied when opening risk.
+    Init,
+    /// Maintenance-margin weights — the threshold below which an account is
+    /// liquidatable.
+    Maint,
+}
+
+/// Per-symbol margin weights. Longs contribute `asset_weight * market_value`;
+/// shorts deduct `liab_weight * |market_value|`, with the liability haircut
+/// depending on the [`HealthType`].
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolWeights {
+    pub asset_weight: Decimal,
+    pub liab_init: Decimal,
+    pub liab_maint: Decimal,
+}
+
+impl Default for SymbolWeights {
+    fn default() -> Self {
+        Self {
+            asset_weight: dec!(1.0),
+            liab_init: dec!(1.5),
+            liab_maint: dec!(1.25),
+        }
+    }
+}
+
+impl SymbolWeights {
+    fn liab_weight(&self, health_type: HealthType) -> Decimal {
+        match health_type {
+            HealthType::Init => self.liab_init,
+            HealthType::Maint => self.liab_maint,
+        }
+    }
+}
+
+/// Which price each side of a valuation is marked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValuationMode {
+    /// Latest observed (oracle) price — today's behavior.
+    Oracle,
+    /// Slow-moving EMA price.
+    Stable,
+    /// Worst-of the two prices per side: stable caps long upside, oracle marks
+    /// losses, so the result is harder to manipulate with a single print.
+    Conservative,
 }
 
 pub struct PortfolioManager {
     portfolios: DashMap<String, Portfolio>,
-    
-    valuation_cache: DashMap<String, (Portfolio, DateTime<Utc>)>,
+
+    // Cached valuations carry the account version they were computed from; a
+    // read observing a newer version treats the entry as stale even before TTL.
+    valuation_cache: DashMap<String, CacheEntry>,
     cache_ttl_seconds: i64,
-    
+
+    // Per-account mutation counter, bumped on every write. Stored alongside the
+    // cache entry so interleaved writes can never leave a stale value readable.
+    versions: DashMap<String, u64>,
+    // Reverse index: symbol -> accounts holding it, so a price update can evict
+    // exactly the affected accounts instead of scanning or waiting for TTL.
+    symbol_holders: DashMap<String, HashSet<String>>,
+
     metrics: Arc<RwLock<HashMap<String, HashMap<String, PositionMetrics>>>>,
     market_prices: DashMap<String, Decimal>,
+    // Slow-moving EMA per symbol; seeded to the first observed price.
+    stable_prices: DashMap<String, Decimal>,
+    // EMA smoothing factor for the stable price: stable += alpha * (oracle - stable).
+    alpha: Decimal,
+    // Per-symbol margin weights; symbols absent here use the default schedule.
+    symbol_weights: DashMap<String, SymbolWeights>,
+    // How cost lots are consumed on reducing fills.
+    lot_method: LotMethod,
+    // Commission schedule charged on each fill.
+    fee_schedule: FeeSchedule,
+    // Currency applied to positions and cash when none is specified.
+    base_currency: String,
+    // Directed FX table keyed by (from, to).
+    fx_rates: DashMap<(String, String), Decimal>,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    portfolio: Portfolio,
+    cached_at: DateTime<Utc>,
+    version: u64,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -55,44 +505,336 @@ impl PortfolioManager {
             portfolios: DashMap::new(),
             valuation_cache: DashMap::new(),
             cache_ttl_seconds,
+            versions: DashMap::new(),
+            symbol_holders: DashMap::new(),
             metrics: Arc::new(RwLock::new(HashMap::new())),
             market_prices: DashMap::new(),
+            stable_prices: DashMap::new(),
+            alpha: dec!(0.2),
+            symbol_weights: DashMap::new(),
+            lot_method: LotMethod::Fifo,
+            fee_schedule: FeeSchedule::default(),
+            base_currency: "USD".to_string(),
+            fx_rates: DashMap::new(),
+        }
+    }
+
+    /// Override the base currency applied to positions and cash (default USD).
+    pub fn with_base_currency(mut self, currency: &str) -> Self {
+        self.base_currency = currency.to_string();
+        self
+    }
+
+    /// Register a directed FX rate so that `1 from == rate to`.
+    pub fn set_fx_rate(&self, from: &str, to: &str, rate: Decimal) {
+        self.fx_rates.insert((from.to_string(), to.to_string()), rate);
+    }
+
+    /// Attach (or replace) a pricing instrument on an existing position.
+    pub fn set_instrument(&self, account_id: &str, symbol: &str, instrument: Instrument) -> Result<()> {
+        let mut portfolio = self.portfolios.get_mut(account_id)
+            .ok_or(PortfolioError::NotFound)?;
+        let position = portfolio.positions.get_mut(symbol)
+            .ok_or(PortfolioError::NotFound)?;
+        position.instrument = Some(instrument);
+        drop(portfolio);
+        self.invalidate(account_id);
+        Ok(())
+    }
+
+    /// Reprice every instrument-backed position from its current market inputs
+    /// as of `as_of`, updating `current_price` and `market_value` so option
+    /// positions reflect spot moves and time decay.
+    pub fn reprice(&self, as_of: NaiveDate) {
+        let mut touched = Vec::new();
+        for mut entry in self.portfolios.iter_mut() {
+            let account_id = entry.key().clone();
+            let mut changed = false;
+            for position in entry.value_mut().positions.values_mut() {
+                if let Some(Instrument::Option(spec)) = &position.instrument {
+                    let (price, _) = price_option(spec, as_of);
+                    position.current_price = price;
+                    position.market_value = price * Decimal::from(position.quantity.abs());
+                    changed = true;
+                }
+            }
+            if changed {
+                touched.push(account_id);
+            }
+        }
+        for account_id in touched {
+            self.invalidate(&account_id);
+        }
+    }
+
+    /// Aggregate greeks across an account's instrument positions,
+    /// each scaled by its (signed) quantity.
+    pub fn portfolio_greeks(&self, account_id: &str, as_of: NaiveDate) -> Result<Greeks> {
+        let portfolio = self.portfolios.get(account_id)
+            .ok_or(PortfolioError::NotFound)?;
+
+        let mut total = Greeks::default();
+        for position in portfolio.positions.values() {
+            if let Some(Instrument::Option(spec)) = &position.instrument {
+                let (_, greeks) = price_option(spec, as_of);
+                let qty = Decimal::from(position.quantity);
+                total.delta += greeks.delta * qty;
+                total.gamma += greeks.gamma * qty;
+                total.vega += greeks.vega * qty;
+                total.theta += greeks.theta * qty;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Resolve the rate to convert `from` into `to`, trying the identity, a
+    /// direct entry, then an inverse entry. Missing cross rates are an error so
+    /// callers detect gaps rather than summing mismatched currencies.
+    fn fx_rate(&self, from: &str, to: &str) -> Result<Decimal> {
+        if from == to {
+            return Ok(Decimal::ONE);
+        }
+        if let Some(rate) = self.fx_rates.get(&(from.to_string(), to.to_string())) {
+            return Ok(*rate);
+        }
+        if let Some(rate) = self.fx_rates.get(&(to.to_string(), from.to_string())) {
+            if !rate.is_zero() {
+                return Ok(Decimal::ONE / *rate);
+            }
+        }
+        anyhow::bail!("missing FX rate {from}->{to}")
+    }
+
+    /// Per-currency market-value totals plus the sum converted into
+    /// `reporting_currency`. Errors if any required cross rate is missing.
+    pub fn portfolio_value_in(&self, account_id: &str, reporting_currency: &str) -> Result<CurrencyReport> {
+        let portfolio = self.get_portfolio(account_id)?;
+
+        let mut per_currency: HashMap<String, Decimal> = HashMap::new();
+        let mut reporting_total = Decimal::ZERO;
+        for pos in portfolio.positions.values() {
+            *per_currency.entry(pos.currency.clone()).or_insert(Decimal::ZERO) += pos.market_value;
+            let rate = self.fx_rate(&pos.currency, reporting_currency)?;
+            reporting_total += pos.market_value * rate;
+        }
+        // Cash is held in the base currency.
+        let cash_rate = self.fx_rate(&self.base_currency, reporting_currency)?;
+        *per_currency.entry(self.base_currency.clone()).or_insert(Decimal::ZERO) += portfolio.cash_balance;
+        reporting_total += portfolio.cash_balance * cash_rate;
+
+        Ok(CurrencyReport {
+            reporting_currency: reporting_currency.to_string(),
+            reporting_total,
+            per_currency,
+        })
+    }
+
+    /// Mark price used to size a rebalance: the latest oracle price, falling
+    /// back to the position's last fill price, and erroring only when neither
+    /// is known (a target symbol we have never seen a price for).
+    fn rebalance_price(&self, symbol: &str, position: Option<&PortfolioPosition>) -> Result<Decimal> {
+        if let Some(price) = self.market_prices.get(symbol) {
+            return Ok(*price);
+        }
+        if let Some(pos) = position {
+            if !pos.current_price.is_zero() {
+                return Ok(pos.current_price);
+            }
+        }
+        anyhow::bail!("no mark price available for {symbol}")
+    }
+
+    /// Current signed weight of each position minus its target weight, as a
+    /// fraction of the account's total value. A positive drift means the
+    /// position is overweight its policy; symbols in `targets` the account does
+    /// not hold report a negative drift of their full target. Lets an agent
+    /// gauge how far the book has wandered before deciding to trade.
+    pub fn drift(&self, account_id: &str, targets: &HashMap<String, Decimal>) -> Result<HashMap<String, Decimal>> {
+        let portfolio = self.get_portfolio(account_id)?;
+        let total = portfolio.total_value;
+
+        let mut drift = HashMap::new();
+        for (symbol, pos) in &portfolio.positions {
+            let signed_mv = pos.market_value * Decimal::from(pos.quantity.signum());
+            let weight = if total.is_zero() { Decimal::ZERO } else { signed_mv / total };
+            let target = targets.get(symbol).copied().unwrap_or(Decimal::ZERO);
+            drift.insert(symbol.clone(), weight - target);
+        }
+        // Targets we hold nothing of are fully drifted below policy.
+        for (symbol, target) in targets {
+            drift.entry(symbol.clone()).or_insert(-*target);
+        }
+        Ok(drift)
+    }
+
+    /// Compute the buy/sell orders that move the account's holdings toward
+    /// `targets` (desired weights as fractions of total value) at current
+    /// marks. Each trade is rounded to whole shares per `config.rounding`, and
+    /// trades whose estimated notional falls under `config.min_trade_notional`
+    /// are suppressed as drift noise. Orders are returned sells-first (to free
+    /// cash before buys), then by symbol for a deterministic sequence.
+    pub fn rebalance(
+        &self,
+        account_id: &str,
+        targets: HashMap<String, Decimal>,
+        config: RebalanceConfig,
+    ) -> Result<Vec<RebalanceTrade>> {
+        let portfolio = self.get_portfolio(account_id)?;
+        let total = portfolio.total_value;
+
+        // Union of currently-held symbols and target symbols, so both new
+        // buys and full exits are considered.
+        let mut symbols: HashSet<String> = portfolio.positions.keys().cloned().collect();
+        symbols.extend(targets.keys().cloned());
+
+        let mut trades = Vec::new();
+        for symbol in symbols {
+            let position = portfolio.positions.get(&symbol);
+            let target_weight = targets.get(&symbol).copied().unwrap_or(Decimal::ZERO);
+
+            let price = self.rebalance_price(&symbol, position)?;
+            let current_value = position
+                .map(|p| p.market_value * Decimal::from(p.quantity.signum()))
+                .unwrap_or(Decimal::ZERO);
+            let target_value = checked_mul(total, target_weight)?;
+            let delta_notional = checked_sub(target_value, current_value)?;
+
+            let raw_shares = checked_div(delta_notional, price)?;
+            let quantity = round_shares(raw_shares, config.rounding);
+            if quantity == 0 {
+                continue;
+            }
+
+            let estimated_notional = checked_mul(Decimal::from(quantity), price)?;
+            if let Some(min) = config.min_trade_notional {
+                if estimated_notional.abs() < min {
+                    continue;
+                }
+            }
+
+            trades.push(RebalanceTrade { symbol, quantity, estimated_notional });
         }
+
+        // Sells before buys, then alphabetically within each side.
+        trades.sort_by(|a, b| {
+            let side = (a.quantity > 0).cmp(&(b.quantity > 0));
+            side.then_with(|| a.symbol.cmp(&b.symbol))
+        });
+        Ok(trades)
+    }
+
+    /// Choose how cost lots are consumed on reducing fills (default FIFO).
+    pub fn with_lot_method(mut self, method: LotMethod) -> Self {
+        self.lot_method = method;
+        self
+    }
+
+    /// Set the commission schedule charged on each fill (default zero).
+    pub fn with_fee_schedule(mut self, schedule: FeeSchedule) -> Self {
+        self.fee_schedule = schedule;
+        self
+    }
+
+    /// Configure the margin weights for a symbol. Symbols left unset use
+    /// [`SymbolWeights::default`] (1.0 for longs, a haircut for shorts).
+    pub fn set_symbol_weights(&self, symbol: &str, weights: SymbolWeights) {
+        self.symbol_weights.insert(symbol.to_string(), weights);
+    }
+
+    /// Weighted account health for the given schedule:
+    /// `cash + Σ(long_mv * asset_weight) − Σ(|short_mv| * liab_weight)`.
+    pub fn account_health(&self, account_id: &str, health_type: HealthType) -> Result<Decimal> {
+        let base_portfolio = self.portfolios.get(account_id)
+            .ok_or(PortfolioError::NotFound)?;
+
+        let mut health = base_portfolio.cash_balance;
+        for (symbol, pos) in &base_portfolio.positions {
+            let price = self.market_prices.get(symbol)
+                .map(|p| *p)
+                .unwrap_or(pos.current_price);
+            let weights = self.symbol_weights.get(symbol)
+                .map(|w| *w)
+                .unwrap_or_default();
+
+            let market_value = checked_mul(price, Decimal::from(pos.quantity.abs()))?;
+            if pos.quantity >= 0 {
+                let contribution = checked_mul(market_value, weights.asset_weight)?;
+                health = checked_add(health, contribution)?;
+            } else {
+                let liability = checked_mul(market_value, weights.liab_weight(health_type))?;
+                health = checked_sub(health, liability)?;
+            }
+        }
+
+        Ok(health)
+    }
+
+    /// An account is liquidatable once its maintenance health goes negative.
+    pub fn is_liquidatable(&self, account_id: &str) -> Result<bool> {
+        Ok(self.account_health(account_id, HealthType::Maint)? < Decimal::ZERO)
+    }
+
+    /// Override the stable-price EMA smoothing factor (default `0.2`).
+    pub fn with_alpha(mut self, alpha: Decimal) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    fn current_version(&self, account_id: &str) -> u64 {
+        self.versions.get(account_id).map(|v| *v).unwrap_or(0)
+    }
+
+    /// Bump an account's version and drop any cached valuation for it.
+    fn invalidate(&self, account_id: &str) {
+        *self.versions.entry(account_id.to_string()).or_insert(0) += 1;
+        self.valuation_cache.remove(account_id);
     }
 
     
     pub fn get_portfolio(&self, account_id: &str) -> Result<Portfolio> {
         let now = Utc::now();
+        let version = self.current_version(account_id);
 
-        // Check cache
+        // Serve from cache only when it is both fresh and computed from the
+        // current account version; a write since then bumps the version and
+        // makes the entry stale, guaranteeing read-after-write consistency.
         if let Some(cached) = self.valuation_cache.get(account_id) {
-            let (portfolio, cached_at) = cached.value();
-            let age = (now - *cached_at).num_seconds();
+            let age = (now - cached.cached_at).num_seconds();
 
-            
             self.record_metric(account_id, true);
 
-            if age < self.cache_ttl_seconds {
-                return Ok(portfolio.clone());
+            if age < self.cache_ttl_seconds && cached.version == version {
+                return Ok(cached.portfolio.clone());
             }
         }
 
-        
-        // No singleflight/coalescing pattern
         self.record_metric(account_id, false);
 
         // Calculate portfolio value
-        let portfolio = self.calculate_portfolio(account_id)?;
+        let portfolio = self.calculate_portfolio(account_id, ValuationMode::Oracle)?;
 
-        
-        self.valuation_cache.insert(account_id.to_string(), (portfolio.clone(), now));
+        self.valuation_cache.insert(
+            account_id.to_string(),
+            CacheEntry { portfolio: portfolio.clone(), cached_at: now, version },
+        );
 
         Ok(portfolio)
     }
 
-    fn calculate_portfolio(&self, account_id: &str) -> Result<Portfolio> {
+    /// Value a portfolio against a chosen [`ValuationMode`]. `Oracle` is the
+    /// cached fast path used by [`Self::get_portfolio`]; the less-manipulable
+    /// modes always recompute so a cached oracle valuation is never confused
+    /// with a stable/conservative one.
+    pub fn get_portfolio_valued(&self, account_id: &str, mode: ValuationMode) -> Result<Portfolio> {
+        if mode == ValuationMode::Oracle {
+            return self.get_portfolio(account_id);
+        }
+        self.calculate_portfolio(account_id, mode)
+    }
+
+    fn calculate_portfolio(&self, account_id: &str, mode: ValuationMode) -> Result<Portfolio> {
         let base_portfolio = self.portfolios.get(account_id)
-            .ok_or_else(|| anyhow::anyhow!("Portfolio not found"))?;
+            .ok_or(PortfolioError::NotFound)?;
 
         let mut positions = HashMap::new();
         let mut total_value = base_portfolio.cash_balance;
@@ -101,22 +843,45 @@ impl PortfolioManager {
             let current_price = self.market_prices.get(symbol)
                 .map(|p| *p)
                 .unwrap_or(pos.current_price);
+            // Stable EMA, seeded to the oracle price so early valuations match.
+            let stable_price = self.stable_prices.get(symbol)
+                .map(|p| *p)
+                .unwrap_or(current_price);
 
-            let market_value = current_price * Decimal::from(pos.quantity.abs());
-            let cost_basis = pos.average_cost * Decimal::from(pos.quantity.abs());
+            // Price the position according to the requested mode. Conservative
+            // uses the worse price for the side held: stable caps a long's
+            // upside, oracle marks a short's liability at the higher price.
+            let valuation_price = match mode {
+                ValuationMode::Oracle => current_price,
+                ValuationMode::Stable => stable_price,
+                ValuationMode::Conservative => {
+                    if pos.quantity >= 0 {
+                        current_price.min(stable_price)
+                    } else {
+                        current_price.max(stable_price)
+                    }
+                }
+            };
+
+            // Every multiply/add/divide goes through the checked-math path so
+            // extreme quantities or prices surface a structured error instead
+            // of silently overflowing or panicking.
+            let abs_qty = Decimal::from(pos.quantity.abs());
+            let market_value = checked_mul(valuation_price, abs_qty)?;
+            let cost_basis = checked_mul(pos.average_cost, abs_qty)?;
             let unrealized_pnl = if pos.quantity > 0 {
-                market_value - cost_basis
+                checked_sub(market_value, cost_basis)?
             } else {
-                cost_basis - market_value
+                checked_sub(cost_basis, market_value)?
             };
 
             let unrealized_pnl_percent = if cost_basis != Decimal::ZERO {
-                (unrealized_pnl / cost_basis) * dec!(100)
+                checked_mul(checked_div(unrealized_pnl, cost_basis)?, dec!(100))?
             } else {
                 Decimal::ZERO
             };
 
-            
+
             self.record_position_metric(account_id, symbol);
 
             positions.insert(symbol.clone(), PortfolioPosition {
@@ -127,9 +892,14 @@ impl PortfolioManager {
                 market_value,
                 unrealized_pnl,
                 unrealized_pnl_percent,
+                stable_price,
+                lots: pos.lots.clone(),
+                currency: pos.currency.clone(),
+                instrument: pos.instrument.clone(),
             });
 
-            total_value += market_value * Decimal::from(pos.quantity.signum());
+            let signed = checked_mul(market_value, Decimal::from(pos.quantity.signum()))?;
+            total_value = checked_add(total_value, signed)?;
         }
 
         Ok(Portfolio {
@@ -137,6 +907,8 @@ impl PortfolioManager {
             positions,
             cash_balance: base_portfolio.cash_balance,
             total_value,
+            realized_pnl: base_portfolio.realized_pnl,
+            fees_paid: base_portfolio.fees_paid,
             last_updated: Utc::now(),
         })
     }
@@ -176,15 +948,25 @@ impl PortfolioManager {
     }
 
     pub fn update_position(&self, account_id: &str, symbol: &str, quantity_delta: i64, price: Decimal) -> Result<()> {
+        let base = self.base_currency.clone();
+        self.update_position_in(account_id, symbol, quantity_delta, price, &base)
+    }
+
+    /// Like [`Self::update_position`] but tags the position with a denomination
+    /// currency. Subsequent fills on the same symbol keep the first currency.
+    pub fn update_position_in(&self, account_id: &str, symbol: &str, quantity_delta: i64, price: Decimal, currency: &str) -> Result<()> {
         let mut portfolio = self.portfolios.entry(account_id.to_string())
             .or_insert(Portfolio {
                 account_id: account_id.to_string(),
                 positions: HashMap::new(),
                 cash_balance: Decimal::ZERO,
                 total_value: Decimal::ZERO,
+                realized_pnl: Decimal::ZERO,
+                fees_paid: Decimal::ZERO,
                 last_updated: Utc::now(),
             });
 
+        let method = self.lot_method;
         let position = portfolio.positions.entry(symbol.to_string())
             .or_insert(PortfolioPosition {
                 symbol: symbol.to_string(),
@@ -194,27 +976,40 @@ impl PortfolioManager {
                 market_value: Decimal::ZERO,
                 unrealized_pnl: Decimal::ZERO,
                 unrealized_pnl_percent: Decimal::ZERO,
+                stable_price: price,
+                lots: Vec::new(),
+                currency: currency.to_string(),
+                instrument: None,
             });
 
-        // Update position
         let old_qty = position.quantity;
-        let new_qty = old_qty + quantity_delta;
-
-        if (old_qty >= 0 && quantity_delta > 0) || (old_qty <= 0 && quantity_delta < 0) {
-            // Adding to position - recalculate average
-            let old_cost = position.average_cost * Decimal::from(old_qty.abs());
-            let new_cost = price * Decimal::from(quantity_delta.abs());
-            if new_qty != 0 {
-                position.average_cost = (old_cost + new_cost) / Decimal::from(new_qty.abs());
-            }
-        }
+        let realized = apply_fill(position, quantity_delta, price, method);
 
-        position.quantity = new_qty;
+        position.quantity = old_qty + quantity_delta;
         position.current_price = price;
+        // Keep the reported average cost consistent with the surviving lots.
+        position.average_cost = weighted_average_cost(&position.lots);
+        portfolio.realized_pnl += realized;
+
+        // Charge commission on the notional of the fill, honoring any cap on
+        // cumulative fees for the account, and deduct it from cash.
+        let mut fee = self.fee_schedule.fee_for(quantity_delta, price);
+        if let Some(cap) = self.fee_schedule.per_account_cap {
+            let remaining = cap - portfolio.fees_paid;
+            fee = fee.min(remaining.max(Decimal::ZERO));
+        }
+        portfolio.fees_paid += fee;
+        portfolio.cash_balance -= fee;
         portfolio.last_updated = Utc::now();
+        drop(portfolio);
 
-        
-        // self.valuation_cache.remove(account_id);
+        // Record the holding so a later price update can target this account,
+        // then invalidate so the next read recomputes.
+        self.symbol_holders
+            .entry(symbol.to_string())
+            .or_default()
+            .insert(account_id.to_string());
+        self.invalidate(account_id);
 
         Ok(())
     }
@@ -222,8 +1017,23 @@ impl PortfolioManager {
     pub fn update_market_price(&self, symbol: &str, price: Decimal) {
         self.market_prices.insert(symbol.to_string(), price);
 
-        
-        // But we don't track which accounts hold which symbols efficiently
+        // Advance the stable EMA, seeding it to the first observed price.
+        match self.stable_prices.get(symbol).map(|s| *s) {
+            Some(stable) => {
+                let next = stable + self.alpha * (price - stable);
+                self.stable_prices.insert(symbol.to_string(), next);
+            }
+            None => {
+                self.stable_prices.insert(symbol.to_string(), price);
+            }
+        }
+
+        // Evict exactly the accounts holding this symbol using the reverse index.
+        if let Some(holders) = self.symbol_holders.get(symbol) {
+            for account_id in holders.iter() {
+                self.invalidate(account_id);
+            }
+        }
     }
 
     pub fn set_cash_balance(&self, account_id: &str, balance: Decimal) -> Result<()> {
@@ -233,41 +1043,21 @@ impl PortfolioManager {
                 positions: HashMap::new(),
                 cash_balance: Decimal::ZERO,
                 total_value: Decimal::ZERO,
+                realized_pnl: Decimal::ZERO,
+                fees_paid: Decimal::ZERO,
                 last_updated: Utc::now(),
             });
 
         portfolio.cash_balance = balance;
         portfolio.last_updated = Utc::now();
+        drop(portfolio);
+
+        self.invalidate(account_id);
 
-        
         Ok(())
     }
 }
 
-// Correct implementation for H2 (cache stampede):
-// Use singleflight pattern or distributed locking:
-//
-// impl PortfolioManager {
-//     pub async fn get_portfolio(&self, account_id: &str) -> Result<Portfolio> {
-//         // Check cache first
-//         if let Some(cached) = self.check_cache(account_id) {
-//             return Ok(cached);
-//         }
-//
-//         // Use singleflight to coalesce concurrent requests
-//         let result = self.singleflight
-//             .work(account_id, || async {
-//                 self.calculate_portfolio(account_id).await
-//             })
-//             .await?;
-//
-//         // Cache result
-//         self.update_cache(account_id, &result);
-//
-//         Ok(result)
-//     }
-// }
-
 // Correct implementation for J2 (metric cardinality):
 // Use bounded labels, not user IDs:
 //
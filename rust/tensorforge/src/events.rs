@@ -1,5 +1,9 @@
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TimedEvent {
@@ -51,42 +55,304 @@ pub fn count_by_kind(events: &[TimedEvent]) -> HashMap<String, usize> {
     kind_ids.into_iter().map(|(k, ids)| (k, ids.len())).collect()  
 }
 
-pub struct EventLog {
-    events: Mutex<Vec<TimedEvent>>,
+/// Pluggable persistence backend for [`EventLog`]. Each backend assigns a
+/// monotonically increasing sequence number on `append` and keeps events
+/// ordered so `filter_time_window`, `detect_gaps` and `batch_events` can be
+/// served by a `range` scan instead of cloning the whole log. Tests use the
+/// in-memory ring buffer; production can use the durable embedded-DB backend.
+pub trait EventStore {
+    /// Persist `event`, returning the sequence number assigned to it.
+    fn append(&self, event: &TimedEvent) -> u64;
+    /// Events with `start <= timestamp <= end`, in stored order.
+    fn range(&self, start: u64, end: u64) -> Vec<TimedEvent>;
+    /// The most recently appended event, if any.
+    fn latest(&self) -> Option<TimedEvent>;
+    /// Events whose sequence number is strictly greater than `seq`, plus the
+    /// new high-water sequence to advance a cursor to.
+    fn since(&self, seq: u64) -> (Vec<TimedEvent>, u64);
+    /// Highest sequence number assigned so far (0 before the first append).
+    fn high_water(&self) -> u64;
+    /// Number of events currently retained.
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Drop all retained events (sequence numbers keep advancing).
+    fn clear(&self);
+}
+
+/// In-memory ring buffer: keeps the most recent `max_size` events, evicting the
+/// oldest once full.
+pub struct MemoryStore {
+    inner: Mutex<std::collections::VecDeque<(u64, TimedEvent)>>,
     max_size: usize,
+    next_seq: AtomicU64,
 }
 
-impl EventLog {
+impl MemoryStore {
     pub fn new(max_size: usize) -> Self {
         Self {
-            events: Mutex::new(Vec::new()),
+            inner: Mutex::new(std::collections::VecDeque::new()),
             max_size,
+            next_seq: AtomicU64::new(1),
         }
     }
+}
 
-    
-    pub fn append(&self, event: TimedEvent) {
-        let mut events = self.events.lock().unwrap();
-        events.push(event);
-        if events.len() > self.max_size {
-            events.pop();  
+impl EventStore for MemoryStore {
+    fn append(&self, event: &TimedEvent) -> u64 {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+        let mut buf = self.inner.lock().unwrap();
+        buf.push_back((seq, event.clone()));
+        // Evict the OLDEST once over capacity (front of the deque).
+        while buf.len() > self.max_size {
+            buf.pop_front();
         }
+        seq
+    }
+
+    fn range(&self, start: u64, end: u64) -> Vec<TimedEvent> {
+        self.inner
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, e)| e.timestamp >= start && e.timestamp <= end)
+            .map(|(_, e)| e.clone())
+            .collect()
+    }
+
+    fn latest(&self) -> Option<TimedEvent> {
+        self.inner.lock().unwrap().back().map(|(_, e)| e.clone())
+    }
+
+    fn since(&self, seq: u64) -> (Vec<TimedEvent>, u64) {
+        let buf = self.inner.lock().unwrap();
+        let mut high = seq;
+        let selected = buf
+            .iter()
+            .filter(|(s, _)| *s > seq)
+            .map(|(s, e)| {
+                high = high.max(*s);
+                e.clone()
+            })
+            .collect();
+        (selected, high)
+    }
+
+    fn high_water(&self) -> u64 {
+        self.next_seq.load(Ordering::SeqCst) - 1
+    }
+
+    fn len(&self) -> usize {
+        self.inner.lock().unwrap().len()
+    }
+
+    fn clear(&self) {
+        self.inner.lock().unwrap().clear();
+    }
+}
+
+/// Durable SQLite backend. Events are keyed by an autoincrement `seq` with a
+/// secondary index on `(timestamp, id)`, so `range` and `since` are served by
+/// index scans rather than full-table reads and the log survives restarts.
+pub struct SqliteStore {
+    conn: Mutex<rusqlite::Connection>,
+}
+
+impl SqliteStore {
+    /// Open (or create) the event database at `path`.
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                 seq       INTEGER PRIMARY KEY AUTOINCREMENT,
+                 id        TEXT NOT NULL,
+                 timestamp INTEGER NOT NULL,
+                 kind      TEXT NOT NULL,
+                 payload   TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_events_ts_id ON events (timestamp, id);",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn row_to_event(row: &rusqlite::Row<'_>) -> rusqlite::Result<(u64, TimedEvent)> {
+        Ok((
+            row.get::<_, i64>(0)? as u64,
+            TimedEvent {
+                id: row.get(1)?,
+                timestamp: row.get::<_, i64>(2)? as u64,
+                kind: row.get(3)?,
+                payload: row.get(4)?,
+            },
+        ))
+    }
+}
+
+impl EventStore for SqliteStore {
+    fn append(&self, event: &TimedEvent) -> u64 {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO events (id, timestamp, kind, payload) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![event.id, event.timestamp as i64, event.kind, event.payload],
+        )
+        .expect("event insert");
+        conn.last_insert_rowid() as u64
+    }
+
+    fn range(&self, start: u64, end: u64) -> Vec<TimedEvent> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT seq, id, timestamp, kind, payload FROM events \
+                 WHERE timestamp >= ?1 AND timestamp <= ?2 ORDER BY timestamp, id",
+            )
+            .expect("prepare range");
+        // Clamp so `u64::MAX` (as used by `get_all`) stays a positive upper bound.
+        let (start, end) = (start.min(i64::MAX as u64), end.min(i64::MAX as u64));
+        let rows = stmt
+            .query_map(rusqlite::params![start as i64, end as i64], Self::row_to_event)
+            .expect("query range");
+        rows.filter_map(Result::ok).map(|(_, e)| e).collect()
+    }
+
+    fn latest(&self) -> Option<TimedEvent> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT seq, id, timestamp, kind, payload FROM events ORDER BY seq DESC LIMIT 1",
+            [],
+            Self::row_to_event,
+        )
+        .ok()
+        .map(|(_, e)| e)
+    }
+
+    fn since(&self, seq: u64) -> (Vec<TimedEvent>, u64) {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT seq, id, timestamp, kind, payload FROM events WHERE seq > ?1 ORDER BY seq")
+            .expect("prepare since");
+        let rows = stmt
+            .query_map(rusqlite::params![seq as i64], Self::row_to_event)
+            .expect("query since");
+        let mut high = seq;
+        let selected = rows
+            .filter_map(Result::ok)
+            .map(|(s, e)| {
+                high = high.max(s);
+                e
+            })
+            .collect();
+        (selected, high)
+    }
+
+    fn high_water(&self) -> u64 {
+        let conn = self.conn.lock().unwrap();
+        // sqlite_sequence keeps the AUTOINCREMENT mark across `clear`.
+        conn.query_row(
+            "SELECT COALESCE((SELECT seq FROM sqlite_sequence WHERE name = 'events'), 0)",
+            [],
+            |r| r.get::<_, i64>(0),
+        )
+        .map(|v| v as u64)
+        .unwrap_or(0)
+    }
+
+    fn len(&self) -> usize {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM events", [], |r| r.get::<_, i64>(0))
+            .map(|v| v as usize)
+            .unwrap_or(0)
+    }
+
+    fn clear(&self) {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM events", []).ok();
+    }
+}
+
+pub struct EventLog {
+    store: Box<dyn EventStore + Send + Sync>,
+    notify: Arc<Notify>,
+}
+
+impl EventLog {
+    /// Build a log backed by the in-memory ring buffer.
+    pub fn new(max_size: usize) -> Self {
+        Self::with_store(Box::new(MemoryStore::new(max_size)))
+    }
+
+    /// Build a log over any persistence backend (e.g. [`SqliteStore`]).
+    pub fn with_store(store: Box<dyn EventStore + Send + Sync>) -> Self {
+        Self {
+            store,
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn append(&self, event: TimedEvent) {
+        self.store.append(&event);
+        // Wake any reader parked in poll_since.
+        self.notify.notify_waiters();
     }
 
     pub fn get_all(&self) -> Vec<TimedEvent> {
-        self.events.lock().unwrap().clone()
+        self.store.range(u64::MIN, u64::MAX)
+    }
+
+    /// Events with `start <= timestamp <= end`, served by a backend range scan.
+    pub fn range(&self, start: u64, end: u64) -> Vec<TimedEvent> {
+        self.store.range(start, end)
     }
 
     pub fn count(&self) -> usize {
-        self.events.lock().unwrap().len()
+        self.store.len()
     }
 
     pub fn clear(&self) {
-        self.events.lock().unwrap().clear();
+        self.store.clear();
     }
 
     pub fn latest(&self) -> Option<TimedEvent> {
-        self.events.lock().unwrap().last().cloned()
+        self.store.latest()
+    }
+
+    /// Highest sequence number assigned so far (0 before the first append).
+    pub fn high_water(&self) -> u64 {
+        self.store.high_water()
+    }
+
+    /// Events whose sequence number is strictly greater than `seq`, along with
+    /// the new high-water mark the caller should advance its cursor to. Returns
+    /// immediately when newer events already exist; otherwise parks on the
+    /// internal [`Notify`] until an append wakes it or `timeout` elapses,
+    /// mirroring a causal-context change feed.
+    pub async fn poll_since(&self, seq: u64, timeout: Duration) -> (Vec<TimedEvent>, u64) {
+        loop {
+            // Register for wakeups BEFORE reading so an append between the read
+            // and the await cannot be missed. `notified()` only arms on first
+            // poll, so enable it eagerly before the `since()` read.
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let (events, high) = self.store.since(seq);
+            if !events.is_empty() {
+                return (events, high);
+            }
+
+            if tokio::time::timeout(timeout, notified).await.is_err() {
+                // Timed out: re-check once in case an append landed in the gap
+                // before returning the empty/no-progress result.
+                let (events, high) = self.store.since(seq);
+                if !events.is_empty() {
+                    return (events, high);
+                }
+                return (Vec::new(), self.high_water());
+            }
+        }
     }
 }
 
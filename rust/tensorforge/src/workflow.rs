@@ -1,5 +1,16 @@
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::sync::Mutex;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Schema version embedded in [`WorkflowEngine::snapshot`] output. Bump this
+/// and add a migration step whenever the set of valid states or allowed
+/// transitions changes across crate versions.
+const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+const ALL_STATES: &[&str] = &["queued", "allocated", "departed", "arrived", "cancelled"];
 
 pub fn can_transition(src: &str, dest: &str) -> bool {
     match src {
@@ -37,7 +48,6 @@ pub fn shortest_path(from: &str, to: &str) -> Option<Vec<String>> {
     if from == to {
         return Some(vec![from.to_string()]);
     }
-    let all_states = ["queued", "allocated", "departed", "arrived", "cancelled"];
     let mut visited = HashSet::new();
     let mut queue: VecDeque<Vec<String>> = VecDeque::new();
     queue.push_back(vec![from.to_string()]);
@@ -50,7 +60,7 @@ pub fn shortest_path(from: &str, to: &str) -> Option<Vec<String>> {
                 result.push(next.to_string());
                 return Some(result);
             }
-            if !visited.contains(next) && all_states.contains(&next) {
+            if !visited.contains(next) && ALL_STATES.contains(&next) {
                 visited.insert(next.to_string());
                 let mut new_path = path.clone();
                 new_path.push(next.to_string());
@@ -61,7 +71,7 @@ pub fn shortest_path(from: &str, to: &str) -> Option<Vec<String>> {
     None
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TransitionRecord {
     pub entity_id: String,
     pub from: String,
@@ -77,9 +87,137 @@ pub struct TransitionResult {
     pub error: Option<String>,
 }
 
+/// A declarative lifecycle rule: an entity sitting in `from` for longer than
+/// `max_age` seconds is advanced to `to` on the next [`WorkflowEngine::tick`].
+#[derive(Clone, Debug)]
+pub struct LifecycleRule {
+    pub from: String,
+    pub max_age: u64,
+    pub to: String,
+}
+
+/// A discrepancy found by [`WorkflowEngine::verify`] between the live entity
+/// map and the recorded transition history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// An entity's current state cannot be reached from `"queued"` by replaying
+    /// its recorded transition chain (a broken or missing link).
+    UnreachableState { entity_id: String, state: String },
+    /// A history record for an entity that had already reached a terminal state.
+    RecordAfterTerminal { entity_id: String, index: usize },
+    /// A history record whose `from`/`to` pair violates [`can_transition`].
+    InvalidTransition {
+        entity_id: String,
+        from: String,
+        to: String,
+        index: usize,
+    },
+    /// An entity that appears in `history` but was never [`register`]ed.
+    ///
+    /// [`register`]: WorkflowEngine::register
+    UnregisteredEntity { entity_id: String },
+}
+
+/// Repair strategy applied by [`WorkflowEngine::repair`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepairPolicy {
+    /// Drop history records recorded after an entity reached a terminal state.
+    TruncateAfterTerminal,
+    /// Drop history records whose transition violates [`can_transition`].
+    DropInvalidRecords,
+    /// Reconcile each entity's current state to the `to` of its last record.
+    ReconcileToHistoryTail,
+}
+
+/// Self-describing, version-tagged on-disk form of a [`WorkflowEngine`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Snapshot {
+    schema_version: u32,
+    entities: HashMap<String, String>,
+    history: Vec<TransitionRecord>,
+}
+
+/// Why a snapshot could not be loaded by [`WorkflowEngine::restore`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MigrateError {
+    /// The byte stream was not a decodable snapshot.
+    Deserialize(String),
+    /// The header named a schema version this build has no migration for.
+    UnknownVersion(u32),
+    /// After migration a value was not a recognized state (see [`is_valid_state`]).
+    InvalidState { entity_id: String, state: String },
+    /// After migration a history record violated [`can_transition`].
+    InvalidRecord { index: usize, from: String, to: String },
+}
+
 pub struct WorkflowEngine {
     entities: Mutex<HashMap<String, String>>,
     history: Mutex<Vec<TransitionRecord>>,
+    edge_costs: Mutex<HashMap<(String, String), f64>>,
+    /// Live per-edge transition counter, bumped inside [`transition`] so the
+    /// Prometheus export never has to re-scan `history`.
+    transition_counts: Mutex<HashMap<(String, String), u64>>,
+    /// Live failure counter partitioned by a normalized error reason.
+    failure_counts: Mutex<HashMap<String, u64>>,
+    /// Upper bounds (inclusive, in seconds) for the time-in-state histogram.
+    histogram_buckets: Mutex<Vec<u64>>,
+    /// Timestamp at which each entity entered its current state, used by
+    /// [`tick`](WorkflowEngine::tick) to age entities against lifecycle rules.
+    entered_at: Mutex<HashMap<String, u64>>,
+    /// Declarative time-based transition rules.
+    lifecycle_rules: Mutex<Vec<LifecycleRule>>,
+    /// Signaled (paired with the `entities` mutex) after every committed state
+    /// change so long-poll waiters in [`WorkflowEngine::poll_until`] wake.
+    transition_cv: Condvar,
+}
+
+/// Handle returned by [`WorkflowEngine::subscribe`]. It captures the entity's
+/// state at subscription time so a later [`Subscription::wait`] blocks only
+/// until the entity leaves *that* state, returning immediately if it already
+/// moved on.
+pub struct Subscription<'a> {
+    engine: &'a WorkflowEngine,
+    entity_id: String,
+    known_state: String,
+}
+
+impl Subscription<'_> {
+    /// Block until the subscribed entity leaves the state it was in when
+    /// [`subscribe`](WorkflowEngine::subscribe) was called, returning the new
+    /// state, or `None` on timeout / if the entity is unknown.
+    pub fn wait(&self, timeout: Duration) -> Option<String> {
+        self.engine
+            .poll_until(&self.entity_id, &self.known_state, timeout)
+    }
+}
+
+/// Priority-queue entry for [`WorkflowEngine::min_cost_path`]. Ordered so the
+/// `BinaryHeap` pops the *lowest* accumulated cost first.
+struct Candidate {
+    cost: f64,
+    state: String,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reverse so the min-cost candidate is the max of the heap.
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
 }
 
 impl WorkflowEngine {
@@ -87,14 +225,32 @@ impl WorkflowEngine {
         Self {
             entities: Mutex::new(HashMap::new()),
             history: Mutex::new(Vec::new()),
+            edge_costs: Mutex::new(HashMap::new()),
+            transition_counts: Mutex::new(HashMap::new()),
+            failure_counts: Mutex::new(HashMap::new()),
+            histogram_buckets: Mutex::new(vec![1, 5, 10, 30, 60, 300]),
+            entered_at: Mutex::new(HashMap::new()),
+            lifecycle_rules: Mutex::new(Vec::new()),
+            transition_cv: Condvar::new(),
         }
     }
 
     pub fn register(&self, entity_id: &str) {
+        self.register_at(entity_id, 0);
+    }
+
+    /// Register an entity, recording `timestamp` as the moment it entered the
+    /// initial `"queued"` state so [`tick`](Self::tick) can age it correctly.
+    /// Plain [`register`](Self::register) assumes entry at time 0.
+    pub fn register_at(&self, entity_id: &str, timestamp: u64) {
         self.entities
             .lock()
             .unwrap()
             .insert(entity_id.to_string(), "queued".to_string());
+        self.entered_at
+            .lock()
+            .unwrap()
+            .insert(entity_id.to_string(), timestamp);
     }
 
     pub fn get_state(&self, entity_id: &str) -> Option<String> {
@@ -106,15 +262,17 @@ impl WorkflowEngine {
         let from = match entities.get(entity_id) {
             Some(s) => s.clone(),
             None => {
+                self.record_failure("not_registered");
                 return TransitionResult {
                     success: false,
                     from: String::new(),
                     to: to.to_string(),
                     error: Some("entity not registered".to_string()),
-                }
+                };
             }
         };
         if !can_transition(&from, to) {
+            self.record_failure("invalid_transition");
             return TransitionResult {
                 success: false,
                 from: from.clone(),
@@ -123,6 +281,10 @@ impl WorkflowEngine {
             };
         }
         entities.insert(entity_id.to_string(), to.to_string());
+        self.entered_at
+            .lock()
+            .unwrap()
+            .insert(entity_id.to_string(), timestamp);
         let record = TransitionRecord {
             entity_id: entity_id.to_string(),
             from: from.clone(),
@@ -130,6 +292,15 @@ impl WorkflowEngine {
             timestamp,
         };
         self.history.lock().unwrap().push(record);
+        *self
+            .transition_counts
+            .lock()
+            .unwrap()
+            .entry((from.clone(), to.to_string()))
+            .or_insert(0) += 1;
+        // Wake every long-poll waiter; terminal transitions included, since a
+        // waiter may be parked on the entity's prior state.
+        self.transition_cv.notify_all();
         TransitionResult {
             success: true,
             from,
@@ -171,6 +342,660 @@ impl WorkflowEngine {
             })
             .collect()
     }
+
+    /// Register the cost of traversing the edge `src -> dest` (e.g. an average
+    /// observed dwell time learned from [`history`](Self::history)). The edge
+    /// need not be legal yet — only [`can_transition`] edges are ever relaxed —
+    /// but the weight must be non-negative so Dijkstra stays correct; negative
+    /// weights are rejected rather than silently corrupting the search.
+    pub fn set_edge_cost(&self, src: &str, dest: &str, weight: f64) -> Result<(), String> {
+        if weight < 0.0 || weight.is_nan() {
+            return Err(format!("edge cost {} -> {} must be non-negative", src, dest));
+        }
+        self.edge_costs
+            .lock()
+            .unwrap()
+            .insert((src.to_string(), dest.to_string()), weight);
+        Ok(())
+    }
+
+    /// Weight of the edge `src -> dest`, defaulting to `1.0` when no cost has
+    /// been registered so an engine with no cost model behaves like the old
+    /// unit-weight BFS.
+    fn edge_cost(&self, src: &str, dest: &str) -> f64 {
+        self.edge_costs
+            .lock()
+            .unwrap()
+            .get(&(src.to_string(), dest.to_string()))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Minimum-cost path from `from` to `to` under the registered edge weights,
+    /// computed with Dijkstra over the five workflow states using a binary-heap
+    /// priority queue keyed on accumulated cost. Only edges permitted by
+    /// [`can_transition`] are relaxed. Returns the state sequence (including both
+    /// endpoints) together with its total cost, `Some((vec![from], 0.0))` for a
+    /// self-path, or `None` when `to` is unreachable.
+    pub fn min_cost_path(&self, from: &str, to: &str) -> Option<(Vec<String>, f64)> {
+        if from == to {
+            return Some((vec![from.to_string()], 0.0));
+        }
+        let mut best: HashMap<String, f64> = HashMap::new();
+        let mut prev: HashMap<String, String> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        best.insert(from.to_string(), 0.0);
+        heap.push(Candidate {
+            cost: 0.0,
+            state: from.to_string(),
+        });
+        while let Some(Candidate { cost, state }) = heap.pop() {
+            if state == to {
+                let mut path = vec![state.clone()];
+                let mut cursor = state;
+                while let Some(p) = prev.get(&cursor) {
+                    path.push(p.clone());
+                    cursor = p.clone();
+                }
+                path.reverse();
+                return Some((path, cost));
+            }
+            // Stale heap entry superseded by a cheaper relaxation.
+            if cost > *best.get(&state).unwrap_or(&f64::INFINITY) {
+                continue;
+            }
+            for next in allowed_transitions(&state) {
+                if !can_transition(&state, next) {
+                    continue;
+                }
+                let ncost = cost + self.edge_cost(&state, next);
+                if ncost < *best.get(next).unwrap_or(&f64::INFINITY) {
+                    best.insert(next.to_string(), ncost);
+                    prev.insert(next.to_string(), state.clone());
+                    heap.push(Candidate {
+                        cost: ncost,
+                        state: next.to_string(),
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Apply a batch of `(entity_id, to, timestamp)` transitions with
+    /// all-or-nothing semantics. Under a single lock on `entities`, a dry-run
+    /// pass validates every tuple against a cloned working copy — checking the
+    /// entity exists and the move is permitted by [`can_transition`] — applying
+    /// each accepted move to the working copy in order so two tuples targeting
+    /// the same entity validate as a dependent chain. Only if *every* tuple
+    /// passes are the new states committed and all [`TransitionRecord`]s appended
+    /// to `history` together; on any failure the batch returns `(false, results)`
+    /// with zero side effects. `results` holds one entry per input tuple.
+    pub fn batch_transition_atomic(
+        &self,
+        transitions: &[(&str, &str, u64)],
+    ) -> (bool, Vec<TransitionResult>) {
+        let mut entities = self.entities.lock().unwrap();
+        let mut working = entities.clone();
+        let mut results = Vec::with_capacity(transitions.len());
+        let mut pending: Vec<TransitionRecord> = Vec::new();
+        let mut all_ok = true;
+
+        for &(entity_id, to, ts) in transitions {
+            let from = match working.get(entity_id) {
+                Some(s) => s.clone(),
+                None => {
+                    all_ok = false;
+                    results.push(TransitionResult {
+                        success: false,
+                        from: String::new(),
+                        to: to.to_string(),
+                        error: Some("entity not registered".to_string()),
+                    });
+                    continue;
+                }
+            };
+            if !can_transition(&from, to) {
+                all_ok = false;
+                results.push(TransitionResult {
+                    success: false,
+                    from: from.clone(),
+                    to: to.to_string(),
+                    error: Some(format!("cannot transition from {} to {}", from, to)),
+                });
+                continue;
+            }
+            working.insert(entity_id.to_string(), to.to_string());
+            pending.push(TransitionRecord {
+                entity_id: entity_id.to_string(),
+                from: from.clone(),
+                to: to.to_string(),
+                timestamp: ts,
+            });
+            results.push(TransitionResult {
+                success: true,
+                from,
+                to: to.to_string(),
+                error: None,
+            });
+        }
+
+        if all_ok {
+            let mut counts = self.transition_counts.lock().unwrap();
+            for r in &pending {
+                *counts.entry((r.from.clone(), r.to.clone())).or_insert(0) += 1;
+            }
+            drop(counts);
+            let mut entered = self.entered_at.lock().unwrap();
+            for r in &pending {
+                entered.insert(r.entity_id.clone(), r.timestamp);
+            }
+            drop(entered);
+            *entities = working;
+            self.history.lock().unwrap().extend(pending);
+            self.transition_cv.notify_all();
+        }
+        (all_ok, results)
+    }
+
+    /// True minimum cost to reach `"arrived"` from `current` under the
+    /// registered edge weights, or `None` if `"arrived"` is unreachable (e.g.
+    /// from a terminal state). Supersedes the uniform `path.len()` estimate of
+    /// the free [`estimated_completion`] helper.
+    pub fn estimated_completion(&self, current: &str) -> Option<f64> {
+        self.min_cost_path(current, "arrived").map(|(_, cost)| cost)
+    }
+
+    /// Subscribe to `entity_id`, snapshotting its current state so a subsequent
+    /// [`Subscription::wait`] blocks only until the entity leaves that state.
+    /// An unknown entity is captured as the empty state: `wait` returns `None`
+    /// while it is still unknown, and its current state once it is registered.
+    pub fn subscribe(&self, entity_id: &str) -> Subscription<'_> {
+        let known_state = self
+            .entities
+            .lock()
+            .unwrap()
+            .get(entity_id)
+            .cloned()
+            .unwrap_or_default();
+        Subscription {
+            engine: self,
+            entity_id: entity_id.to_string(),
+            known_state,
+        }
+    }
+
+    /// Block until `entity_id` leaves `known_state`, returning the new state, or
+    /// `None` on timeout or if the entity is unknown. Parks on the transition
+    /// [`Condvar`] paired with the entities mutex instead of spin-polling
+    /// [`get_state`](Self::get_state); if the entity already moved past
+    /// `known_state` before the call, it returns immediately.
+    pub fn poll_until(
+        &self,
+        entity_id: &str,
+        known_state: &str,
+        timeout: Duration,
+    ) -> Option<String> {
+        let deadline = Instant::now() + timeout;
+        let mut guard = self.entities.lock().unwrap();
+        loop {
+            match guard.get(entity_id) {
+                None => return None,
+                Some(s) if s != known_state => return Some(s.clone()),
+                _ => {}
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            let (g, res) = self
+                .transition_cv
+                .wait_timeout(guard, deadline - now)
+                .unwrap();
+            guard = g;
+            if res.timed_out() {
+                return match guard.get(entity_id) {
+                    Some(s) if s != known_state => Some(s.clone()),
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    fn record_failure(&self, reason: &str) {
+        *self
+            .failure_counts
+            .lock()
+            .unwrap()
+            .entry(reason.to_string())
+            .or_insert(0) += 1;
+    }
+
+    /// Override the inclusive upper bounds (in seconds) of the time-in-state
+    /// histogram exported by [`metrics_text`](Self::metrics_text). Bounds are
+    /// sorted; an implicit `+Inf` bucket is always appended.
+    pub fn set_histogram_buckets(&self, mut buckets: Vec<u64>) {
+        buckets.sort_unstable();
+        buckets.dedup();
+        *self.histogram_buckets.lock().unwrap() = buckets;
+    }
+
+    /// Render the engine's operational metrics in Prometheus text exposition
+    /// format. Transition and failure counters reflect the live counts bumped
+    /// inside [`transition`](Self::transition); the gauges snapshot the current
+    /// entity map; the time-in-state histogram is aggregated lazily from
+    /// `history` over consecutive same-entity records.
+    pub fn metrics_text(&self) -> String {
+        let mut out = String::new();
+
+        // --- Per-edge transition counter ---
+        out.push_str("# HELP workflow_transitions_total Total successful transitions per edge.\n");
+        out.push_str("# TYPE workflow_transitions_total counter\n");
+        let edges = self.transition_counts.lock().unwrap();
+        let mut edge_keys: Vec<_> = edges.keys().cloned().collect();
+        edge_keys.sort();
+        for (from, to) in edge_keys {
+            let v = edges[&(from.clone(), to.clone())];
+            out.push_str(&format!(
+                "workflow_transitions_total{{from=\"{}\",to=\"{}\"}} {}\n",
+                from, to, v
+            ));
+        }
+        drop(edges);
+
+        // --- Failure counter by reason ---
+        out.push_str("# HELP workflow_transition_failures_total Rejected transitions by reason.\n");
+        out.push_str("# TYPE workflow_transition_failures_total counter\n");
+        let failures = self.failure_counts.lock().unwrap();
+        let mut reasons: Vec<_> = failures.keys().cloned().collect();
+        reasons.sort();
+        for reason in reasons {
+            out.push_str(&format!(
+                "workflow_transition_failures_total{{reason=\"{}\"}} {}\n",
+                reason, failures[&reason]
+            ));
+        }
+        drop(failures);
+
+        // --- Gauges from the current entity map ---
+        let entities = self.entities.lock().unwrap().clone();
+        let active = entities.values().filter(|s| !is_terminal_state(s)).count();
+        out.push_str("# HELP workflow_active_entities Entities not in a terminal state.\n");
+        out.push_str("# TYPE workflow_active_entities gauge\n");
+        out.push_str(&format!("workflow_active_entities {}\n", active));
+
+        let dist = state_distribution(&entities);
+        out.push_str("# HELP workflow_entities_in_state Entities currently in each state.\n");
+        out.push_str("# TYPE workflow_entities_in_state gauge\n");
+        for state in ALL_STATES {
+            let v = dist.get(*state).copied().unwrap_or(0);
+            out.push_str(&format!(
+                "workflow_entities_in_state{{state=\"{}\"}} {}\n",
+                state, v
+            ));
+        }
+
+        // --- Time-in-state histogram (lazy aggregation over history) ---
+        let buckets = self.histogram_buckets.lock().unwrap().clone();
+        let durations = self.time_in_state_samples();
+        let mut counts = vec![0u64; buckets.len()];
+        let mut inf = 0u64;
+        let mut sum = 0u64;
+        for d in &durations {
+            sum += *d;
+            match buckets.iter().position(|b| *d <= *b) {
+                Some(i) => counts[i] += 1,
+                None => inf += 1,
+            }
+        }
+        out.push_str("# HELP workflow_time_in_state_seconds Observed time entities spent in a state.\n");
+        out.push_str("# TYPE workflow_time_in_state_seconds histogram\n");
+        let mut cumulative = 0u64;
+        for (i, b) in buckets.iter().enumerate() {
+            cumulative += counts[i];
+            out.push_str(&format!(
+                "workflow_time_in_state_seconds_bucket{{le=\"{}\"}} {}\n",
+                b, cumulative
+            ));
+        }
+        cumulative += inf;
+        out.push_str(&format!(
+            "workflow_time_in_state_seconds_bucket{{le=\"+Inf\"}} {}\n",
+            cumulative
+        ));
+        out.push_str(&format!("workflow_time_in_state_seconds_sum {}\n", sum));
+        out.push_str(&format!(
+            "workflow_time_in_state_seconds_count {}\n",
+            durations.len()
+        ));
+
+        out
+    }
+
+    /// Per-entity time-in-state samples drawn from consecutive `history`
+    /// records: the gap between record `i` and `i+1` is the time entity spent
+    /// in record `i`'s destination state.
+    fn time_in_state_samples(&self) -> Vec<u64> {
+        let history = self.history.lock().unwrap();
+        let mut by_entity: HashMap<String, Vec<&TransitionRecord>> = HashMap::new();
+        for r in history.iter() {
+            by_entity.entry(r.entity_id.clone()).or_default().push(r);
+        }
+        let mut samples = Vec::new();
+        for records in by_entity.values_mut() {
+            records.sort_by_key(|r| r.timestamp);
+            for pair in records.windows(2) {
+                samples.push(pair[1].timestamp.saturating_sub(pair[0].timestamp));
+            }
+        }
+        samples
+    }
+
+    /// Register a lifecycle rule: an entity that has been in `from` for longer
+    /// than `max_age` seconds is advanced to `to` on the next [`tick`](Self::tick).
+    pub fn register_rule(&self, from: &str, max_age: u64, to: &str) {
+        self.lifecycle_rules.lock().unwrap().push(LifecycleRule {
+            from: from.to_string(),
+            max_age,
+            to: to.to_string(),
+        });
+    }
+
+    /// Cross-check the live `entities` map against the recorded `history`,
+    /// returning every discrepancy found: current states unreachable from
+    /// `"queued"` by replaying the recorded chain, records logged after an
+    /// entity reached a terminal state, records whose transition violates
+    /// [`can_transition`], and entities that appear in history but were never
+    /// registered. The engine is left untouched — call [`repair`](Self::repair)
+    /// to act on the findings.
+    pub fn verify(&self) -> Vec<Inconsistency> {
+        let entities = self.entities.lock().unwrap();
+        let history = self.history.lock().unwrap();
+        let mut issues = Vec::new();
+
+        // Per-entity record indices in timestamp order (stable on ties).
+        let mut by_entity: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, r) in history.iter().enumerate() {
+            by_entity.entry(r.entity_id.clone()).or_default().push(i);
+        }
+        for idxs in by_entity.values_mut() {
+            idxs.sort_by_key(|&i| history[i].timestamp);
+        }
+        let mut entity_keys: Vec<&String> = by_entity.keys().collect();
+        entity_keys.sort();
+
+        // (c) invalid-transition corruption and (b) records after terminal.
+        for entity in &entity_keys {
+            let mut terminal_reached = false;
+            for &i in &by_entity[*entity] {
+                let r = &history[i];
+                if !can_transition(&r.from, &r.to) {
+                    issues.push(Inconsistency::InvalidTransition {
+                        entity_id: (*entity).clone(),
+                        from: r.from.clone(),
+                        to: r.to.clone(),
+                        index: i,
+                    });
+                }
+                if terminal_reached {
+                    issues.push(Inconsistency::RecordAfterTerminal {
+                        entity_id: (*entity).clone(),
+                        index: i,
+                    });
+                }
+                if is_terminal_state(&r.to) {
+                    terminal_reached = true;
+                }
+            }
+        }
+
+        // (d) entities present in history but never registered.
+        for entity in &entity_keys {
+            if !entities.contains_key(*entity) {
+                issues.push(Inconsistency::UnregisteredEntity {
+                    entity_id: (*entity).clone(),
+                });
+            }
+        }
+
+        // (a) current state unreachable from "queued" via the recorded chain.
+        let mut live_keys: Vec<&String> = entities.keys().collect();
+        live_keys.sort();
+        for entity in live_keys {
+            let state = &entities[entity];
+            let mut cur = "queued".to_string();
+            let mut broken = false;
+            if let Some(idxs) = by_entity.get(entity) {
+                for &i in idxs {
+                    let r = &history[i];
+                    if r.from != cur {
+                        broken = true;
+                        break;
+                    }
+                    cur = r.to.clone();
+                }
+            }
+            if broken || &cur != state {
+                issues.push(Inconsistency::UnreachableState {
+                    entity_id: entity.clone(),
+                    state: state.clone(),
+                });
+            }
+        }
+        issues
+    }
+
+    /// Apply `policy` under lock and return a human-readable summary of the
+    /// actions taken. See [`RepairPolicy`] for the available strategies.
+    pub fn repair(&self, policy: RepairPolicy) -> Vec<String> {
+        let mut entities = self.entities.lock().unwrap();
+        let mut history = self.history.lock().unwrap();
+        let mut actions = Vec::new();
+        match policy {
+            RepairPolicy::DropInvalidRecords => {
+                let before = history.len();
+                history.retain(|r| can_transition(&r.from, &r.to));
+                let dropped = before - history.len();
+                if dropped > 0 {
+                    actions.push(format!("dropped {} invalid transition record(s)", dropped));
+                }
+            }
+            RepairPolicy::TruncateAfterTerminal => {
+                let mut by_entity: HashMap<String, Vec<usize>> = HashMap::new();
+                for (i, r) in history.iter().enumerate() {
+                    by_entity.entry(r.entity_id.clone()).or_default().push(i);
+                }
+                let mut drop_set = HashSet::new();
+                for idxs in by_entity.values_mut() {
+                    idxs.sort_by_key(|&i| history[i].timestamp);
+                    let mut terminal = false;
+                    for &i in idxs.iter() {
+                        if terminal {
+                            drop_set.insert(i);
+                        }
+                        if is_terminal_state(&history[i].to) {
+                            terminal = true;
+                        }
+                    }
+                }
+                if !drop_set.is_empty() {
+                    let kept: Vec<TransitionRecord> = history
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| !drop_set.contains(i))
+                        .map(|(_, r)| r.clone())
+                        .collect();
+                    actions.push(format!(
+                        "truncated {} record(s) after terminal state",
+                        drop_set.len()
+                    ));
+                    *history = kept;
+                }
+            }
+            RepairPolicy::ReconcileToHistoryTail => {
+                let mut tail: HashMap<String, (u64, String)> = HashMap::new();
+                for r in history.iter() {
+                    let slot = tail
+                        .entry(r.entity_id.clone())
+                        .or_insert((r.timestamp, r.to.clone()));
+                    if r.timestamp >= slot.0 {
+                        *slot = (r.timestamp, r.to.clone());
+                    }
+                }
+                let mut count = 0;
+                for (entity, state) in entities.iter_mut() {
+                    if let Some((_, to)) = tail.get(entity) {
+                        if state != to {
+                            *state = to.clone();
+                            count += 1;
+                        }
+                    }
+                }
+                if count > 0 {
+                    actions.push(format!("reconciled {} entity state(s) to history tail", count));
+                }
+            }
+        }
+        actions
+    }
+
+    /// Age every non-terminal entity against the registered lifecycle rules at
+    /// wall-clock `now`. An entity whose time in its current state exceeds a
+    /// matching rule's `max_age` (via [`is_stale`]) is advanced through the
+    /// normal [`transition`](Self::transition) path, so history, metrics and
+    /// validity checks all still apply — rules that would violate
+    /// [`can_transition`] never fire. At most one rule fires per entity per tick.
+    pub fn tick(&self, now: u64) -> Vec<TransitionResult> {
+        let rules = self.lifecycle_rules.lock().unwrap().clone();
+        let snapshot: Vec<(String, String, u64)> = {
+            let entities = self.entities.lock().unwrap();
+            let entered = self.entered_at.lock().unwrap();
+            entities
+                .iter()
+                .filter(|(_, s)| !is_terminal_state(s))
+                .map(|(id, s)| (id.clone(), s.clone(), entered.get(id).copied().unwrap_or(0)))
+                .collect()
+        };
+        let mut results = Vec::new();
+        for (id, state, entry) in snapshot {
+            for rule in &rules {
+                if rule.from == state
+                    && can_transition(&state, &rule.to)
+                    && is_stale(entry, now, rule.max_age)
+                {
+                    results.push(self.transition(&id, &rule.to, now));
+                    break;
+                }
+            }
+        }
+        results
+    }
+
+    /// Serialize `entities` and `history` into a self-describing, version-tagged
+    /// byte stream that [`restore`](Self::restore) can load — and migrate —
+    /// across crate versions.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let snap = Snapshot {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            entities: self.entities.lock().unwrap().clone(),
+            history: self.history.lock().unwrap().clone(),
+        };
+        serde_json::to_vec(&snap).expect("snapshot serialization")
+    }
+
+    /// Load a snapshot produced by [`snapshot`](Self::snapshot), upgrading it
+    /// through the migration chain to the current schema first. After migration
+    /// every state and record is validated against the current rules; the first
+    /// value that is not a valid state or whose transition violates
+    /// [`can_transition`] is surfaced as a [`MigrateError`] rather than loaded.
+    pub fn restore(bytes: &[u8]) -> Result<WorkflowEngine, MigrateError> {
+        let mut snap: Snapshot =
+            serde_json::from_slice(bytes).map_err(|e| MigrateError::Deserialize(e.to_string()))?;
+
+        // Run the migration chain up to the current schema version.
+        while snap.schema_version < CURRENT_SCHEMA_VERSION {
+            match snap.schema_version {
+                1 => {
+                    migrate_v1_to_v2(&mut snap);
+                    snap.schema_version = 2;
+                }
+                other => return Err(MigrateError::UnknownVersion(other)),
+            }
+        }
+        if snap.schema_version != CURRENT_SCHEMA_VERSION {
+            return Err(MigrateError::UnknownVersion(snap.schema_version));
+        }
+
+        // Validate migrated state against the current rules.
+        let mut live_keys: Vec<&String> = snap.entities.keys().collect();
+        live_keys.sort();
+        for id in live_keys {
+            let state = &snap.entities[id];
+            if !is_valid_state(state) {
+                return Err(MigrateError::InvalidState {
+                    entity_id: id.clone(),
+                    state: state.clone(),
+                });
+            }
+        }
+        for (i, r) in snap.history.iter().enumerate() {
+            if !is_valid_state(&r.from) {
+                return Err(MigrateError::InvalidState {
+                    entity_id: r.entity_id.clone(),
+                    state: r.from.clone(),
+                });
+            }
+            if !is_valid_state(&r.to) {
+                return Err(MigrateError::InvalidState {
+                    entity_id: r.entity_id.clone(),
+                    state: r.to.clone(),
+                });
+            }
+            if !can_transition(&r.from, &r.to) {
+                return Err(MigrateError::InvalidRecord {
+                    index: i,
+                    from: r.from.clone(),
+                    to: r.to.clone(),
+                });
+            }
+        }
+
+        // Reconstruct entry timestamps from each entity's latest record.
+        let mut entered: HashMap<String, u64> = HashMap::new();
+        for r in &snap.history {
+            let slot = entered.entry(r.entity_id.clone()).or_insert(r.timestamp);
+            if r.timestamp >= *slot {
+                *slot = r.timestamp;
+            }
+        }
+        for id in snap.entities.keys() {
+            entered.entry(id.clone()).or_insert(0);
+        }
+
+        let engine = WorkflowEngine::new();
+        *engine.entities.lock().unwrap() = snap.entities;
+        *engine.history.lock().unwrap() = snap.history;
+        *engine.entered_at.lock().unwrap() = entered;
+        Ok(engine)
+    }
+}
+
+/// Migrate a v1 snapshot to v2: v1 called the second state `"assigned"`, which
+/// v2 renamed to `"allocated"`. Remap it everywhere so the records validate
+/// against the current transition rules.
+fn migrate_v1_to_v2(snap: &mut Snapshot) {
+    let remap = |s: &mut String| {
+        if s == "assigned" {
+            *s = "allocated".to_string();
+        }
+    };
+    for state in snap.entities.values_mut() {
+        remap(state);
+    }
+    for r in snap.history.iter_mut() {
+        remap(&mut r.from);
+        remap(&mut r.to);
+    }
 }
 
 
@@ -342,14 +1167,5 @@ pub fn batch_transition_atomic(
     engine: &WorkflowEngine,
     transitions: &[(&str, &str, u64)],
 ) -> (bool, Vec<TransitionResult>) {
-    let mut results = Vec::new();
-    let mut all_ok = true;
-    for &(entity_id, to, ts) in transitions {
-        let result = engine.transition(entity_id, to, ts);
-        if !result.success {
-            all_ok = false;
-        }
-        results.push(result);
-    }
-    (all_ok, results)
+    engine.batch_transition_atomic(transitions)
 }
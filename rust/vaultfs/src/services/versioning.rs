@@ -1,10 +1,12 @@
 use crate::models::file::FileMetadata;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 pub struct VersioningService {
     versions: Arc<RwLock<HashMap<String, Vec<FileVersion>>>>,
+    chunks: Arc<RwLock<ChunkStore>>,
 }
 
 #[derive(Clone, Debug)]
@@ -13,17 +15,93 @@ pub struct FileVersion {
     pub file_id: String,
     pub hash: String,
     pub size: usize,
+    // Ordered content-defined chunk digests. Unchanged chunks are shared with
+    // other versions through the chunk store, so near-identical snapshots only
+    // pay for the bytes that actually changed.
+    pub chunks: Vec<String>,
+    // Merkle root over the chunk digests, recorded so stored content can be
+    // verified and the first corrupted chunk located without a full re-hash.
+    pub merkle_root: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// A declarative, S3-lifecycle-style retention rule applied to files whose id
+/// starts with `file_id_prefix`. Any unset threshold is simply not enforced.
+#[derive(Clone, Debug)]
+pub struct LifecycleRule {
+    pub file_id_prefix: String,
+    pub noncurrent_versions_to_keep: Option<usize>,
+    pub expire_noncurrent_after: Option<std::time::Duration>,
+    pub expire_current_after: Option<std::time::Duration>,
+}
+
+/// Summary of one lifecycle pass.
+#[derive(Clone, Debug, Default)]
+pub struct LifecycleSummary {
+    pub versions_deleted: usize,
+    pub files_affected: usize,
+}
+
+/// Age of a version relative to `now`, saturating at zero for clock skew.
+fn age(now: chrono::DateTime<chrono::Utc>, created_at: chrono::DateTime<chrono::Utc>) -> std::time::Duration {
+    (now - created_at).to_std().unwrap_or_default()
+}
+
+/// Refcounted content-addressed store shared by every version. A chunk is held
+/// once regardless of how many versions reference it; the count drops to zero
+/// (and the bytes are freed) only when the last referencing version is pruned.
+#[derive(Default)]
+struct ChunkStore {
+    chunks: HashMap<String, (Arc<[u8]>, usize)>,
+}
+
+impl ChunkStore {
+    /// Store `data`, returning its digest. A chunk already present just has its
+    /// refcount bumped, so identical content is deduplicated across versions.
+    fn insert(&mut self, data: &[u8]) -> String {
+        let key = chunk_digest(data);
+        let entry = self
+            .chunks
+            .entry(key.clone())
+            .or_insert_with(|| (Arc::from(data.to_vec().into_boxed_slice()), 0));
+        entry.1 += 1;
+        key
+    }
+
+    /// Fetch the bytes stored under `key`, if present.
+    fn get(&self, key: &str) -> Option<Arc<[u8]>> {
+        self.chunks.get(key).map(|(bytes, _)| bytes.clone())
+    }
+
+    /// Drop one reference to `key`, freeing the bytes when the count hits zero.
+    fn release(&mut self, key: &str) {
+        if let Some(entry) = self.chunks.get_mut(key) {
+            entry.1 = entry.1.saturating_sub(1);
+            if entry.1 == 0 {
+                self.chunks.remove(key);
+            }
+        }
+    }
+}
+
 impl VersioningService {
     pub fn new() -> Self {
         Self {
             versions: Arc::new(RwLock::new(HashMap::new())),
+            chunks: Arc::new(RwLock::new(ChunkStore::default())),
         }
     }
 
-    pub async fn create_version(&self, file_id: &str, metadata: &FileMetadata) {
+    pub async fn create_version(&self, file_id: &str, metadata: &FileMetadata, content: &[u8]) {
+        // Split the content into content-defined chunks and fold each into the
+        // shared store; the version records only the ordered chunk digests.
+        let chunk_hashes = {
+            let mut store = self.chunks.write().await;
+            chunk_boundaries(content)
+                .map(|(start, end)| store.insert(&content[start..end]))
+                .collect::<Vec<_>>()
+        };
+
         let mut versions = self.versions.write().await;
 
         // Get or create version list for this file
@@ -31,20 +109,59 @@ impl VersioningService {
             .entry(file_id.to_string())
             .or_insert_with(Vec::new);
 
-        
+
         let next_version = self.calculate_next_version(file_versions);
 
-        
+
         // when we try to push
+        let merkle_root = merkle_root(&chunk_hashes);
         file_versions.push(FileVersion {
             version_number: next_version,
             file_id: file_id.to_string(),
             hash: metadata.hash.clone(),
             size: metadata.size,
+            chunks: chunk_hashes,
+            merkle_root,
             created_at: chrono::Utc::now(),
         });
     }
 
+    /// Recompute the Merkle tree for a stored version from the chunk store and
+    /// compare it against the recorded root. On success the content is intact;
+    /// on mismatch `Err(index)` is the position of the first corrupted (or
+    /// missing) chunk. An unknown version, or a recorded root that differs
+    /// although every chunk matches its digest, also yields `Err(0)`.
+    pub async fn verify_version(&self, file_id: &str, version: u64) -> Result<(), usize> {
+        let recorded = {
+            let versions = self.versions.read().await;
+            match versions
+                .get(file_id)
+                .and_then(|vs| vs.iter().find(|v| v.version_number == version))
+            {
+                Some(v) => v.clone(),
+                None => return Err(0),
+            }
+        };
+
+        let store = self.chunks.read().await;
+        let mut recomputed = Vec::with_capacity(recorded.chunks.len());
+        for (i, expected) in recorded.chunks.iter().enumerate() {
+            match store.get(expected) {
+                Some(bytes) if chunk_digest(&bytes) == *expected => recomputed.push(expected.clone()),
+                // Missing bytes or a digest that no longer matches = corruption.
+                _ => return Err(i),
+            }
+        }
+
+        if merkle_root(&recomputed) == recorded.merkle_root {
+            Ok(())
+        } else {
+            // Every chunk matched its own digest but the root differs: the
+            // chunk ordering itself was tampered with.
+            Err(0)
+        }
+    }
+
     
     fn calculate_next_version(&self, versions: &mut Vec<FileVersion>) -> u64 {
         
@@ -59,11 +176,18 @@ impl VersioningService {
         let mut versions = self.versions.write().await;
 
         if let Some(file_versions) = versions.get_mut(file_id) {
-            
+
             let to_delete = self.get_versions_to_delete(file_versions, keep_count);
 
-            
+            let mut store = self.chunks.write().await;
             for version_num in to_delete {
+                // Release each pruned version's chunks before dropping it so the
+                // store can reclaim any chunk no other version still references.
+                if let Some(v) = file_versions.iter().find(|v| v.version_number == version_num) {
+                    for chunk in &v.chunks {
+                        store.release(chunk);
+                    }
+                }
                 file_versions.retain(|v| v.version_number != version_num);
             }
         }
@@ -80,6 +204,105 @@ impl VersioningService {
             .collect()
     }
 
+    /// Apply `rules` once, deleting noncurrent versions that exceed a rule's
+    /// retention count or age threshold and current versions past their own
+    /// expiry. Rules are evaluated in the order given (first match wins per
+    /// file); files matching no prefix are left untouched. Returns a summary of
+    /// what was deleted this run.
+    pub async fn apply_lifecycle_rules(&self, rules: &[LifecycleRule]) -> LifecycleSummary {
+        let now = chrono::Utc::now();
+        let mut summary = LifecycleSummary::default();
+
+        let mut versions = self.versions.write().await;
+        let mut store = self.chunks.write().await;
+
+        for (file_id, file_versions) in versions.iter_mut() {
+            let Some(rule) = rules.iter().find(|r| file_id.starts_with(&r.file_id_prefix)) else {
+                continue;
+            };
+
+            // The highest version number is the current one; the rest are
+            // noncurrent and subject to count/age retention.
+            file_versions.sort_by_key(|v| v.version_number);
+            let current_number = match file_versions.last() {
+                Some(v) => v.version_number,
+                None => continue,
+            };
+
+            let noncurrent_count = file_versions.len().saturating_sub(1);
+            let mut deleted = 0usize;
+            file_versions.retain(|v| {
+                if v.version_number == current_number {
+                    // Current version only expires on its own threshold.
+                    if let Some(ttl) = rule.expire_current_after {
+                        if age(now, v.created_at) > ttl {
+                            for chunk in &v.chunks {
+                                store.release(chunk);
+                            }
+                            deleted += 1;
+                            return false;
+                        }
+                    }
+                    return true;
+                }
+
+                // Noncurrent: delete past the age threshold or once we have more
+                // kept copies than the rule allows.
+                let too_old = rule
+                    .expire_noncurrent_after
+                    .is_some_and(|ttl| age(now, v.created_at) > ttl);
+                let kept_so_far = noncurrent_count - deleted;
+                let over_count = rule
+                    .noncurrent_versions_to_keep
+                    .is_some_and(|keep| kept_so_far > keep);
+
+                if too_old || over_count {
+                    for chunk in &v.chunks {
+                        store.release(chunk);
+                    }
+                    deleted += 1;
+                    false
+                } else {
+                    true
+                }
+            });
+
+            summary.versions_deleted += deleted;
+            if deleted > 0 {
+                summary.files_affected += 1;
+            }
+        }
+
+        summary
+    }
+
+    /// Spawn a background worker that applies `rules` every `interval`, logging a
+    /// per-run summary. The task runs until the returned handle is aborted;
+    /// dropping the handle detaches the task without stopping it.
+    pub fn spawn_lifecycle_worker(
+        &self,
+        rules: Vec<LifecycleRule>,
+        interval: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let versions = self.versions.clone();
+        let chunks = self.chunks.clone();
+        tokio::spawn(async move {
+            let service = VersioningService { versions, chunks };
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let summary = service.apply_lifecycle_rules(&rules).await;
+                if summary.versions_deleted > 0 {
+                    tracing::info!(
+                        "lifecycle: deleted {} versions across {} files",
+                        summary.versions_deleted,
+                        summary.files_affected
+                    );
+                }
+            }
+        })
+    }
+
     pub async fn get_version(&self, file_id: &str, version: u64) -> Option<FileVersion> {
         let versions = self.versions.read().await;
         versions
@@ -98,6 +321,112 @@ impl VersioningService {
     }
 }
 
+// Content-defined chunking (Gear-hash rolling fingerprint).
+//
+// A cut point is declared when the rolling fingerprint has enough low bits
+// clear. Normalized chunking applies a stricter mask (more 1-bits, so cuts are
+// rarer) until the target size is reached and a looser mask afterwards, which
+// tightens the chunk-size distribution around the average. Lengths are clamped
+// to [MIN_SIZE, MAX_SIZE] to bound both dedup granularity and variance.
+
+const MIN_SIZE: usize = 2 * 1024;
+const AVG_SIZE: usize = 8 * 1024;
+const MAX_SIZE: usize = 64 * 1024;
+// P(cut) ≈ 1/32768 before the target size, 1/8192 after, so cuts are rarer
+// while a chunk is still short and more likely once it is near the average.
+const MASK_STRICT: u64 = (1 << 15) - 1;
+const MASK_LOOSE: u64 = (1 << 13) - 1;
+
+/// Gear table: one random 64-bit value per byte value, generated with a fixed
+/// SplitMix64 seed so chunk boundaries are deterministic across runs.
+const GEAR: [u64; 256] = build_gear();
+
+const fn build_gear() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Iterate the `(start, end)` byte ranges of each content-defined chunk.
+fn chunk_boundaries(data: &[u8]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        if start >= data.len() {
+            return None;
+        }
+        let end = next_cut(&data[start..]).map(|len| start + len).unwrap_or(data.len());
+        let range = (start, end);
+        start = end;
+        Some(range)
+    })
+}
+
+/// Length of the next chunk within `data`, honouring the min/avg/max bounds.
+fn next_cut(data: &[u8]) -> Option<usize> {
+    if data.is_empty() {
+        return None;
+    }
+    if data.len() <= MIN_SIZE {
+        return Some(data.len());
+    }
+    let limit = data.len().min(MAX_SIZE);
+    let avg = data.len().min(AVG_SIZE);
+
+    let mut fp: u64 = 0;
+    let mut i = MIN_SIZE;
+    while i < limit {
+        fp = (fp << 1).wrapping_add(GEAR[data[i] as usize]);
+        let mask = if i < avg { MASK_STRICT } else { MASK_LOOSE };
+        if fp & mask == 0 {
+            return Some(i + 1);
+        }
+        i += 1;
+    }
+    Some(limit)
+}
+
+/// Stable content digest used as the chunk-store key.
+fn chunk_digest(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hex::encode(hasher.finalize())
+}
+
+/// Merkle root over ordered chunk digests: hash each pair of child digests,
+/// carrying a lone trailing node up unchanged, until a single root remains.
+/// An empty chunk list hashes to the digest of no bytes.
+fn merkle_root(leaves: &[String]) -> String {
+    if leaves.is_empty() {
+        return chunk_digest(&[]);
+    }
+    let mut level: Vec<String> = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut hasher = Sha256::new();
+                    hasher.update(left.as_bytes());
+                    hasher.update(right.as_bytes());
+                    hex::encode(hasher.finalize())
+                }
+                [single] => single.clone(),
+                _ => unreachable!("chunks(2) yields 1 or 2 elements"),
+            })
+            .collect();
+    }
+    level.into_iter().next().unwrap()
+}
+
 // Correct implementation:
 // fn calculate_next_version(versions: &[FileVersion]) -> u64 {
 //     // Take immutable reference instead
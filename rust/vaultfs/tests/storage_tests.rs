@@ -70,7 +70,7 @@ async fn test_versioning_create_no_double_borrow() {
     let metadata = FileMetadata::new("test-file");
 
     // Must not panic or fail to compile
-    service.create_version("test-file", &metadata).await;
+    service.create_version("test-file", &metadata, &[]).await;
 
     // Verify version was created
     let versions = service.list_versions("test-file").await;
@@ -86,7 +86,7 @@ async fn test_versioning_prune_no_double_borrow() {
 
     // Create several versions
     for _ in 0..5 {
-        service.create_version("prune-file", &metadata).await;
+        service.create_version("prune-file", &metadata, &[]).await;
     }
 
     // Prune to keep only 2 - must not panic from double borrow